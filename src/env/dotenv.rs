@@ -0,0 +1,131 @@
+//! Parsing of `.env`-style files.
+
+use std::io;
+
+/// Parses the `KEY=value` lines of a `.env`-style file, returning each pair in order.
+///
+/// Blank lines and lines starting with `#` are ignored, as is an `export ` prefix before the key.
+/// Values may be:
+///   * unquoted, in which case surrounding whitespace and any trailing comment (a `#` preceded by
+///     whitespace) are removed;
+///   * single-quoted, in which case the contents are taken literally;
+///   * double-quoted, in which case the escapes `\n`, `\r`, `\t`, `\\` and `\"` are recognized.
+///
+/// A quoted value may be followed only by whitespace or a comment. An error of kind
+/// `io::ErrorKind::InvalidData` is returned for a line which can't be parsed.
+pub(crate) fn parse(contents: &str) -> io::Result<Vec<(String, String)>> {
+    let mut vars = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |message: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: {}", index + 1, message),
+            )
+        };
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| invalid("expected KEY=value"))?;
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(invalid("the variable name is empty"));
+        }
+        let value = parse_value(value.trim_start()).map_err(invalid)?;
+        vars.push((key.to_owned(), value));
+    }
+    Ok(vars)
+}
+
+fn parse_value(value: &str) -> Result<String, &'static str> {
+    let mut chars = value.char_indices();
+    let quote = match chars.next() {
+        Some((_, quote)) if quote == '"' || quote == '\'' => quote,
+        _ => {
+            let end = value
+                .char_indices()
+                .find(|&(i, c)| c == '#' && value[..i].ends_with(char::is_whitespace))
+                .map_or(value.len(), |(i, _)| i);
+            return Ok(value[..end].trim_end().to_owned());
+        }
+    };
+
+    let mut result = String::new();
+    loop {
+        let (i, c) = chars.next().ok_or("the quoted value is not terminated")?;
+        if c == quote {
+            let rest = value[i + 1..].trim_start();
+            if rest.is_empty() || rest.starts_with('#') {
+                return Ok(result);
+            }
+            return Err("unexpected characters after the quoted value");
+        }
+        if c == '\\' && quote == '"' {
+            let (_, escaped) = chars.next().ok_or("the quoted value is not terminated")?;
+            result.push(match escaped {
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                '\\' | '"' => escaped,
+                _ => return Err("unknown escape sequence in the quoted value"),
+            });
+        } else {
+            result.push(c);
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+    use super::parse;
+    use std::io;
+
+    fn pairs(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|&(k, v)| (k.to_owned(), v.to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn parse__comments_and_blank_lines__ignored() {
+        let result = parse("# comment\n\nA=1 # trailing\n  # indented\nexport B=two#three\n");
+
+        assert_eq!(pairs(&[("A", "1"), ("B", "two#three")]), result.unwrap());
+    }
+
+    #[test]
+    fn parse__quoted_values__unquoted_and_unescaped() {
+        let result = parse(
+            "A=\"hello world\"\nB='single $X \\n'\nC=\"line\\nnext \\\"q\\\"\" # note\nD=\"\"\n",
+        );
+
+        assert_eq!(
+            pairs(&[
+                ("A", "hello world"),
+                ("B", "single $X \\n"),
+                ("C", "line\nnext \"q\""),
+                ("D", ""),
+            ]),
+            result.unwrap()
+        );
+    }
+
+    #[test]
+    fn parse__invalid_lines__invalid_data() {
+        for contents in &[
+            "NO_EQUALS",
+            "=value",
+            "A=\"unterminated",
+            "A=\"x\" y",
+            "A=\"\\q\"",
+        ] {
+            let error = parse(contents).unwrap_err();
+            assert_eq!(io::ErrorKind::InvalidData, error.kind(), "{}", contents);
+            assert!(error.to_string().starts_with("line 1: "));
+        }
+    }
+}
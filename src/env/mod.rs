@@ -1,16 +1,83 @@
 //! Defines traits and implementations for the inspection and manipulation of the process's
 //! environment.
 
+mod dotenv;
 mod native;
 mod simulated;
 
 pub use self::native::NativeEnv;
-pub use self::simulated::SimulatedEnv;
+pub use self::simulated::{EnvSnapshot, SimulatedEnv};
 
 use std::env;
 use std::ffi;
 use std::io;
+use std::iter;
 use std::path::{Path, PathBuf};
+use std::vec;
+
+/// A family of operating systems, as reported by [`Env::platform()`](trait.Env.html#method.platform).
+///
+/// This allows code which branches on the platform at runtime to be tested for every platform on
+/// one host, using [`SimulatedEnv::set_platform()`](struct.SimulatedEnv.html#method.set_platform).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Platform {
+    /// Microsoft Windows.
+    Windows,
+    /// A Unix-like operating system, such as Linux or macOS.
+    Unix,
+    /// WebAssembly, e.g. WASI.
+    Wasm,
+    /// Any other platform.
+    Other,
+}
+
+impl Platform {
+    /// Returns the platform which this crate was compiled for.
+    pub fn current() -> Platform {
+        if cfg!(windows) {
+            Platform::Windows
+        } else if cfg!(unix) {
+            Platform::Unix
+        } else if cfg!(target_family = "wasm") {
+            Platform::Wasm
+        } else {
+            Platform::Other
+        }
+    }
+
+    /// Returns the main path separator of this platform: `\` on Windows, and `/` elsewhere.
+    pub fn path_separator(self) -> char {
+        match self {
+            Platform::Windows => '\\',
+            _ => '/',
+        }
+    }
+}
+
+/// Returns the variables of `vars` whose names are in `allow`, converted to strings. Only the
+/// allowed variables are converted, so other variables needn't be valid unicode.
+///
+/// When `case_insensitive` is set, names are compared after converting them to uppercase, the
+/// same folding `SimulatedEnv` uses to look variables up.
+fn filter_vars<I: Iterator<Item = (ffi::OsString, ffi::OsString)>>(
+    vars: I,
+    allow: &[&str],
+    case_insensitive: bool,
+) -> vec::IntoIter<(String, String)> {
+    let allowed = |k: &str| {
+        allow.iter().any(|a| {
+            if case_insensitive {
+                a.to_uppercase() == k.to_uppercase()
+            } else {
+                *a == k
+            }
+        })
+    };
+    vars.filter(|(k, _)| k.to_str().is_some_and(allowed))
+        .map(|(k, v)| (k.into_string().unwrap(), v.into_string().unwrap()))
+        .collect::<Vec<_>>()
+        .into_iter()
+}
 
 /// Provides inspection and manipulation of the process's environment.
 ///
@@ -50,9 +117,15 @@ use std::path::{Path, PathBuf};
 /// ```
 pub trait Env {
     /// The iterator type returned by `args()`.
+    ///
+    /// This isn't required to be `Send` since `std::env::Args` isn't; use `args_vec()` to get the
+    /// arguments in a form which can be moved to another thread.
     type ArgsIter: Iterator<Item = String>;
 
     /// The iterator type returned by `args_os()`.
+    ///
+    /// This isn't required to be `Send` since `std::env::ArgsOs` isn't; use `args_os_vec()` to get
+    /// the arguments in a form which can be moved to another thread.
     type ArgsOsIter: Iterator<Item = ffi::OsString>;
 
     /// The iterator type returned by `vars()`.
@@ -74,6 +147,70 @@ pub trait Env {
     /// information.
     fn args_os(&self) -> Self::ArgsOsIter;
 
+    /// Returns the arguments which this program was started with as an owned `Vec`, which can be
+    /// moved to another thread.
+    ///
+    /// See [`args()`](#tymethod.args) for more information.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use std::thread;
+    /// use io_providers::{Env, SimulatedEnv};
+    ///
+    /// let mut env = SimulatedEnv::new();
+    /// env.set_args(vec!["app".to_owned(), "--verbose".to_owned()]);
+    ///
+    /// let args = env.args_vec();
+    /// let count = thread::spawn(move || args.len()).join().unwrap();
+    /// assert_eq!(2, count);
+    /// ```
+    fn args_vec(&self) -> Vec<String> {
+        self.args().collect()
+    }
+
+    /// Returns the arguments which this program was started with as an owned `Vec`, which can be
+    /// moved to another thread.
+    ///
+    /// See [`args_os()`](#tymethod.args_os) for more information.
+    fn args_os_vec(&self) -> Vec<ffi::OsString> {
+        self.args_os().collect()
+    }
+
+    /// Returns the first argument which this program was started with, which is conventionally
+    /// the name used to invoke it, or `None` if there are no arguments.
+    ///
+    /// The program name is whatever the caller passed as `argv[0]`, so it may not be related to
+    /// the path of the executable; use [`current_exe()`](#tymethod.current_exe) for that.
+    ///
+    /// This returns an owned `String` rather than a `&str`, since providers such as `NativeEnv`
+    /// don't store the arguments and so have nothing to borrow from; `SimulatedEnv` also provides
+    /// borrowing versions, [`program_name_ref()`](struct.SimulatedEnv.html#method.program_name_ref)
+    /// and [`args_after_program_ref()`](struct.SimulatedEnv.html#method.args_after_program_ref).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use io_providers::{Env, SimulatedEnv};
+    ///
+    /// let mut env = SimulatedEnv::new();
+    /// env.set_args(vec!["app".to_owned(), "--verbose".to_owned()]);
+    /// assert_eq!(Some("app".to_owned()), env.program_name());
+    /// assert_eq!(vec!["--verbose".to_owned()], env.args_after_program().collect::<Vec<_>>());
+    /// ```
+    fn program_name(&self) -> Option<String> {
+        self.args().next()
+    }
+
+    /// Returns the arguments which this program was started with, excluding the program name
+    /// (i.e. the first argument).
+    ///
+    /// If there are no arguments at all, the returned iterator is empty, as it is when there is
+    /// only a program name. See [`program_name()`](#method.program_name) for more information.
+    fn args_after_program(&self) -> iter::Skip<Self::ArgsIter> {
+        self.args().skip(1)
+    }
+
     /// Returns the current working directory as a `PathBuf`.
     ///
     /// See [`std::env::current_dir`](https://doc.rust-lang.org/std/env/fn.current_dir.html) for
@@ -143,4 +280,83 @@ pub trait Env {
     ///
     /// See [`std::env::vars_os`](https://doc.rust-lang.org/std/env/fn.vars_os.html) for more information.
     fn vars_os(&self) -> Self::VarsOsIter;
+
+    /// Returns the platform which the program is running on.
+    ///
+    /// By default this is [`Platform::current()`](enum.Platform.html#method.current), the
+    /// platform which this crate was compiled for; `SimulatedEnv` returns the platform set using
+    /// [`set_platform()`](struct.SimulatedEnv.html#method.set_platform).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use io_providers::env::{Env, Platform, SimulatedEnv};
+    ///
+    /// fn join<E: Env>(env: &E, dir: &str, file: &str) -> String {
+    ///     format!("{}{}{}", dir, env.platform().path_separator(), file)
+    /// }
+    ///
+    /// let mut env = SimulatedEnv::new();
+    /// env.set_platform(Platform::Windows);
+    /// assert_eq!("logs\\today.txt", join(&env, "logs", "today.txt"));
+    /// ```
+    fn platform(&self) -> Platform {
+        Platform::current()
+    }
+
+    /// Returns the (variable, value) pairs of the environment variables of the current process
+    /// whose names are in `allow`, e.g. to pass a sanitized environment to a subprocess.
+    ///
+    /// The variables are yielded in the same order as by [`vars()`](#tymethod.vars). Names are
+    /// compared exactly, except that case is ignored on Windows (as reported by
+    /// [`platform()`](#method.platform)) and by a `SimulatedEnv` with case-insensitive names,
+    /// matching how variables are looked up. Variables which aren't allowed may have names or
+    /// values which aren't valid unicode, but this panics if an allowed variable's value isn't.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use io_providers::{Env, SimulatedEnv};
+    ///
+    /// let mut env = SimulatedEnv::new();
+    /// env.set_var("PATH", "/usr/bin");
+    /// env.set_var("API_TOKEN", "secret");
+    ///
+    /// let vars: Vec<_> = env.vars_filtered(&["PATH", "HOME"]).collect();
+    /// assert_eq!(vec![("PATH".to_owned(), "/usr/bin".to_owned())], vars);
+    /// ```
+    fn vars_filtered(&self, allow: &[&str]) -> vec::IntoIter<(String, String)> {
+        filter_vars(self.vars_os(), allow, self.platform() == Platform::Windows)
+    }
+
+    /// Makes a path absolute by joining it onto the current working directory if it is relative,
+    /// without accessing the filesystem.
+    ///
+    /// Unlike `canonicalize()`, the path doesn't need to exist and symbolic links are not
+    /// resolved. The `.` and `..` components of a relative path are normalized lexically, so
+    /// `a/../b` becomes `<current_dir>/b` even if `a` is a symbolic link. Absolute paths are
+    /// returned unchanged. An error is returned if the current working directory can't be
+    /// determined.
+    ///
+    /// This is similar to
+    /// [`std::path::absolute`](https://doc.rust-lang.org/std/path/fn.absolute.html), but uses this
+    /// provider's current working directory.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use io_providers::{Env, SimulatedEnv};
+    ///
+    /// let mut env = SimulatedEnv::new();
+    /// env.set_current_dir("/foo/bar").unwrap();
+    /// assert_eq!(Path::new("/foo/baz/a.txt"), env.absolute_path("../baz/./a.txt").unwrap());
+    /// ```
+    fn absolute_path<P: AsRef<Path>>(&self, path: P) -> io::Result<PathBuf> {
+        let path = path.as_ref();
+        if path.is_absolute() {
+            return Ok(path.to_path_buf());
+        }
+        Ok(::paths::normalize(&self.current_dir()?.join(path)))
+    }
 }
@@ -18,55 +18,68 @@ impl Env for NativeEnv {
     type VarsOsIter = env::VarsOs;
 
     fn args(&self) -> Self::ArgsIter {
+        ::check_real_io_allowed("NativeEnv");
         std::env::args()
     }
 
     fn args_os(&self) -> Self::ArgsOsIter {
+        ::check_real_io_allowed("NativeEnv");
         std::env::args_os()
     }
 
     fn current_dir(&self) -> io::Result<PathBuf> {
+        ::check_real_io_allowed("NativeEnv");
         std::env::current_dir()
     }
 
     fn current_exe(&self) -> io::Result<PathBuf> {
+        ::check_real_io_allowed("NativeEnv");
         std::env::current_exe()
     }
 
     fn home_dir(&self) -> Option<PathBuf> {
+        ::check_real_io_allowed("NativeEnv");
         #[allow(deprecated)]
         std::env::home_dir()
     }
 
     fn remove_var<K: AsRef<ffi::OsStr>>(&mut self, k: K) {
+        ::check_real_io_allowed("NativeEnv");
         std::env::remove_var(k)
     }
 
     fn set_current_dir<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        ::check_real_io_allowed("NativeEnv");
         std::env::set_current_dir(path)
     }
 
     fn set_var<K: AsRef<ffi::OsStr>, V: AsRef<ffi::OsStr>>(&mut self, k: K, v: V) {
+        ::check_real_io_allowed("NativeEnv");
         std::env::set_var(k, v)
     }
 
     fn temp_dir(&self) -> PathBuf {
+        ::check_real_io_allowed("NativeEnv");
         std::env::temp_dir()
     }
 
     fn var<K: AsRef<ffi::OsStr>>(&self, key: K) -> Result<String, env::VarError> {
+        ::check_real_io_allowed("NativeEnv");
         std::env::var(key)
     }
 
     fn var_os<K: AsRef<ffi::OsStr>>(&self, key: K) -> Option<ffi::OsString> {
+        ::check_real_io_allowed("NativeEnv");
         std::env::var_os(key)
     }
 
     fn vars(&self) -> Self::VarsIter {
+        ::check_real_io_allowed("NativeEnv");
         std::env::vars()
     }
 
     fn vars_os(&self) -> Self::VarsOsIter {
+        ::check_real_io_allowed("NativeEnv");
         std::env::vars_os()
     }
 }
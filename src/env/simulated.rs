@@ -1,14 +1,36 @@
-use std::collections::HashMap;
+use std::collections::{btree_map, BTreeMap};
 use std::env;
 use std::ffi;
 use std::io;
+use std::iter;
+use std::mem;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use std::vec;
 
-use env::Env;
+use env::{self as env_mod, dotenv, Env, Platform};
+use fs::Fs;
 
 /// Provides inspection and manipulation of a simulated process environment.
-#[derive(Default)]
+///
+/// Environment variable names are case-insensitive when targeting Windows (so `Path` and `PATH`
+/// refer to the same variable) and case-sensitive everywhere else, matching the semantics of the
+/// real platform. This can be overridden using
+/// [`set_case_insensitive_vars()`](#method.set_case_insensitive_vars).
+///
+/// Since `Env::set_var()` accepts any `OsStr`, a variable's value may be set to a string which
+/// isn't valid unicode, e.g. using `OsStringExt::from_vec()` on Unix. As with `std::env`, `var()`
+/// then returns `Err(VarError::NotUnicode(value))` while `var_os()` returns the value unchanged,
+/// and `vars()` panics.
+///
+/// Getters for values which haven't been set (such as `current_dir()`) panic by default; see
+/// [`set_defer_missing_values()`](#method.set_defer_missing_values) to collect these accesses and
+/// report them all at once instead.
+///
+/// The iterators returned by `args()`, `args_os()`, `vars()` and `vars_os()` own their data, so
+/// unlike those of `NativeEnv` they are `Send` and `'static`. `vars()` and `vars_os()` yield the
+/// variables sorted by name (by uppercased name while names are case-insensitive), regardless of
+/// the order in which they were set, so output derived from them is reproducible.
 pub struct SimulatedEnv {
     args: Option<Vec<String>>,
     args_os: Option<Vec<ffi::OsString>>,
@@ -16,7 +38,33 @@ pub struct SimulatedEnv {
     current_exe: Option<PathBuf>,
     home_dir: Option<PathBuf>,
     temp_dir: Option<PathBuf>,
-    vars: HashMap<ffi::OsString, ffi::OsString>,
+    case_insensitive_vars: bool,
+    platform: Platform,
+    // Maps the (possibly case-folded) lookup key to the original key and the value
+    vars: BTreeMap<ffi::OsString, (ffi::OsString, ffi::OsString)>,
+    defer_missing_values: bool,
+    missing_values: Mutex<Vec<String>>,
+    var_reads: Mutex<Vec<(ffi::OsString, bool)>>,
+    // Behind a Mutex so that the (only Send) listeners don't stop SimulatedEnv from being Sync
+    current_dir_listeners: Mutex<Vec<CurrentDirListener>>,
+}
+
+type CurrentDirListener = Box<dyn FnMut(Option<&Path>, &Path) + Send>;
+
+/// A snapshot of the complete state of a [`SimulatedEnv`](struct.SimulatedEnv.html).
+///
+/// See [`SimulatedEnv::snapshot()`](struct.SimulatedEnv.html#method.snapshot).
+#[derive(Clone, Debug)]
+pub struct EnvSnapshot {
+    args: Option<Vec<String>>,
+    args_os: Option<Vec<ffi::OsString>>,
+    current_dir: Option<PathBuf>,
+    current_exe: Option<PathBuf>,
+    home_dir: Option<PathBuf>,
+    temp_dir: Option<PathBuf>,
+    case_insensitive_vars: bool,
+    platform: Platform,
+    vars: BTreeMap<ffi::OsString, (ffi::OsString, ffi::OsString)>,
 }
 
 impl SimulatedEnv {
@@ -29,25 +77,321 @@ impl SimulatedEnv {
             current_exe: None,
             home_dir: None,
             temp_dir: None,
-            vars: HashMap::new(),
+            case_insensitive_vars: cfg!(windows),
+            platform: Platform::current(),
+            vars: BTreeMap::new(),
+            defer_missing_values: false,
+            missing_values: Mutex::new(Vec::new()),
+            var_reads: Mutex::new(Vec::new()),
+            current_dir_listeners: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Registers a callback which is invoked whenever the current working directory is changed using
+    /// [`Env::set_current_dir()`](trait.Env.html#tymethod.set_current_dir).
+    ///
+    /// The callback is given the previous directory (or `None` if it hadn't been set) and the new
+    /// directory. Callbacks are invoked in the order in which they were registered, after the
+    /// change has been made. Restoring a [`snapshot()`](#method.snapshot) doesn't invoke them.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use std::path::{Path, PathBuf};
+    /// use std::sync::{Arc, Mutex};
+    /// use io_providers::{Env, SimulatedEnv};
+    ///
+    /// let changes = Arc::new(Mutex::new(Vec::new()));
+    /// let mut env = SimulatedEnv::new();
+    /// let recorder = changes.clone();
+    /// env.on_current_dir_change(move |_, new| recorder.lock().unwrap().push(new.to_path_buf()));
+    ///
+    /// env.set_current_dir("/foo").unwrap();
+    /// assert_eq!(vec![PathBuf::from("/foo")], *changes.lock().unwrap());
+    /// ```
+    pub fn on_current_dir_change<F: FnMut(Option<&Path>, &Path) + Send + 'static>(
+        &mut self,
+        listener: F,
+    ) {
+        self.current_dir_listeners
+            .get_mut()
+            .unwrap()
+            .push(Box::new(listener));
+    }
+
+    /// Sets whether accessing a value which hasn't been set is recorded rather than causing a
+    /// panic.
+    ///
+    /// When enabled, such accesses are recorded for [`check()`](#method.check) and a placeholder
+    /// is returned instead: `args()` and `args_os()` return no arguments, `current_dir()` and
+    /// `current_exe()` return an error of kind `io::ErrorKind::NotFound`, and `temp_dir()` returns
+    /// an empty path. This makes it possible to learn about every missing value in a single run
+    /// of a test, rather than fixing the panics one at a time.
+    pub fn set_defer_missing_values(&mut self, defer: bool) {
+        self.defer_missing_values = defer;
+    }
+
+    /// Reports every access to a value which hasn't been set, as recorded while
+    /// [`set_defer_missing_values()`](#method.set_defer_missing_values) is enabled.
+    ///
+    /// Returns `Ok(())` if there were no such accesses, otherwise the name of each getter which
+    /// was called, in the order in which each was first accessed.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use io_providers::{Env, SimulatedEnv};
+    ///
+    /// let mut env = SimulatedEnv::new();
+    /// env.set_defer_missing_values(true);
+    /// env.set_args(vec!["app".to_owned()]);
+    ///
+    /// let _ = env.args();
+    /// let _ = env.current_dir();
+    /// let _ = env.temp_dir();
+    ///
+    /// assert_eq!(
+    ///     Err(vec!["Env::current_dir()".to_owned(), "Env::temp_dir()".to_owned()]),
+    ///     env.check());
+    /// ```
+    pub fn check(&self) -> Result<(), Vec<String>> {
+        let missing = self.missing_values.lock().unwrap();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(missing.clone())
+        }
+    }
+
+    /// Returns every read of a single environment variable using `Env::var()` or `Env::var_os()`,
+    /// in order, as the key which was queried and whether the variable was present.
+    ///
+    /// This makes it possible to assert that code reads only the variables it is supposed to.
+    /// Listing the variables using `Env::vars()` or `Env::vars_os()` isn't recorded, and neither
+    /// are accesses to the simulated environment through its own methods (such as
+    /// [`has_var()`](#method.has_var)).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use std::ffi::OsString;
+    /// use io_providers::{Env, SimulatedEnv};
+    ///
+    /// let mut env = SimulatedEnv::new();
+    /// env.set_var("HOME", "/home/user");
+    ///
+    /// let _ = env.var("HOME");
+    /// let _ = env.var_os("EDITOR");
+    ///
+    /// assert_eq!(
+    ///     vec![(OsString::from("HOME"), true), (OsString::from("EDITOR"), false)],
+    ///     env.var_reads());
+    /// assert!(!env.var_reads().iter().any(|(key, _)| key == "AWS_SECRET_ACCESS_KEY"));
+    /// ```
+    pub fn var_reads(&self) -> Vec<(ffi::OsString, bool)> {
+        self.var_reads.lock().unwrap().clone()
+    }
+
+    /// Clears the log of environment variable reads returned by
+    /// [`var_reads()`](#method.var_reads).
+    pub fn clear_var_reads(&mut self) {
+        self.var_reads.lock().unwrap().clear();
+    }
+
+    /// Sets the platform to be returned by `Env::platform()`; by default, the platform which this
+    /// crate was compiled for.
+    ///
+    /// This only changes what `Env::platform()` reports. In particular, it doesn't change whether
+    /// variable names are case-insensitive (see
+    /// [`set_case_insensitive_vars()`](#method.set_case_insensitive_vars)) or how paths are parsed
+    /// by `std::path`, which always follows the host.
+    pub fn set_platform(&mut self, platform: Platform) {
+        self.platform = platform;
+    }
+
+    /// Sets whether environment variable names are treated case-insensitively.
+    ///
+    /// By default this is `true` when targeting Windows and `false` otherwise. When enabled,
+    /// variables are looked up and replaced without regard to case, but
+    /// [`Env::vars()`](trait.Env.html#tymethod.vars) still reports each name with the casing used
+    /// when it was first set.
+    ///
+    /// Enabling this while several variables have names which differ only in case (such as `PATH`
+    /// and `Path`) merges them: the variable whose name sorts first by its bytes (`PATH`, since
+    /// uppercase letters sort before lowercase ones) is kept, and the others are removed and
+    /// returned as (name, value) pairs. Nothing is returned otherwise.
+    pub fn set_case_insensitive_vars(
+        &mut self,
+        case_insensitive: bool,
+    ) -> Vec<(ffi::OsString, ffi::OsString)> {
+        self.case_insensitive_vars = case_insensitive;
+        let mut vars = mem::take(&mut self.vars).into_values().collect::<Vec<_>>();
+        vars.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let mut collisions = Vec::new();
+        for (k, v) in vars {
+            let key = self.var_key(&k);
+            match self.vars.entry(key) {
+                btree_map::Entry::Occupied(_) => collisions.push((k, v)),
+                btree_map::Entry::Vacant(entry) => {
+                    let _ = entry.insert((k, v));
+                }
+            }
         }
+        collisions
     }
 
     /// Sets the arguments which this program was started with (normally passed via the command
     /// line).
+    ///
+    /// An empty `Vec` is valid, and models a process launched without any arguments (not even
+    /// the program name), in which case `Env::args()` yields nothing. This is distinct from the
+    /// arguments not having been set at all, in which case `Env::args()` panics.
     pub fn set_args(&mut self, args: Vec<String>) {
         self.args = Some(args);
     }
 
     /// Sets the arguments which this program was started with (normally passed via the command
     /// line).
+    ///
+    /// As with [`set_args()`](#method.set_args), an empty `Vec` models a process launched without
+    /// any arguments.
     pub fn set_args_os(&mut self, args: Vec<ffi::OsString>) {
         self.args_os = Some(args);
     }
 
     /// Sets the path to be returned by `Env::current_exe()`.
+    ///
+    /// The path is returned as given, even if it is a symbolic link; see
+    /// [`set_current_exe_resolved()`](#method.set_current_exe_resolved) to resolve it like the OS
+    /// does instead.
     pub fn set_current_exe<P: AsRef<Path>>(&mut self, path: P) {
-        self.current_dir = Some(PathBuf::from(path.as_ref()));
+        self.current_exe = Some(PathBuf::from(path.as_ref()));
+    }
+
+    /// Sets the path to be returned by `Env::current_exe()` to `path` with all of its symbolic
+    /// links resolved through `fs`, like the real `current_exe()` on Linux (which resolves
+    /// `/proc/self/exe`).
+    ///
+    /// The path is resolved once, when this is called, so later changes to `fs` aren't reflected.
+    /// Relative link targets are resolved against the directory containing the link. Every
+    /// component of the path must exist in `fs`; otherwise an error is returned, and the path to
+    /// be returned by `Env::current_exe()` is left unchanged.
+    ///
+    /// Note that the targets of the links in a [`TempFs`](../fs/struct.TempFs.html) are stored by
+    /// the host, so an absolute target is only meaningful within the `TempFs` if it was written as
+    /// a path within the `TempFs` (e.g. by creating the link through the host with a relative
+    /// target).
+    pub fn set_current_exe_resolved<F: Fs, P: AsRef<Path>>(
+        &mut self,
+        fs: &F,
+        path: P,
+    ) -> io::Result<()> {
+        self.current_exe = Some(::paths::resolve_symlinks(fs, path.as_ref())?);
+        Ok(())
+    }
+
+    /// Returns the arguments to be returned by `Env::args()` without cloning them.
+    ///
+    /// Like `Env::args()`, this panics if the arguments haven't been set (or records the access,
+    /// see [`set_defer_missing_values()`](#method.set_defer_missing_values)).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use io_providers::SimulatedEnv;
+    ///
+    /// let mut env = SimulatedEnv::new();
+    /// env.set_args(vec!["app".to_owned(), "--verbose".to_owned()]);
+    /// assert!(env.args_ref().iter().any(|arg| arg == "--verbose"));
+    /// ```
+    pub fn args_ref(&self) -> &[String] {
+        match self.args {
+            Some(ref args) => args,
+            None => self.missing_value("Env::args()", &[]),
+        }
+    }
+
+    /// Returns the arguments to be returned by `Env::args_os()` without cloning them.
+    ///
+    /// See [`args_ref()`](#method.args_ref) for more information.
+    pub fn args_os_ref(&self) -> &[ffi::OsString] {
+        match self.args_os {
+            Some(ref args) => args,
+            None => self.missing_value("Env::args_os()", &[]),
+        }
+    }
+
+    /// Returns the program name to be returned by `Env::program_name()` without cloning it.
+    ///
+    /// See [`args_ref()`](#method.args_ref) for more information.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use io_providers::SimulatedEnv;
+    ///
+    /// let mut env = SimulatedEnv::new();
+    /// env.set_args(vec!["app".to_owned(), "--verbose".to_owned()]);
+    /// assert_eq!(Some("app"), env.program_name_ref());
+    /// assert_eq!(&["--verbose".to_owned()], env.args_after_program_ref());
+    /// ```
+    pub fn program_name_ref(&self) -> Option<&str> {
+        self.args_ref().first().map(String::as_str)
+    }
+
+    /// Returns the arguments to be returned by `Env::args_after_program()` without cloning them;
+    /// the slice is empty if there are no arguments.
+    ///
+    /// See [`args_ref()`](#method.args_ref) for more information.
+    pub fn args_after_program_ref(&self) -> &[String] {
+        self.args_ref().get(1..).unwrap_or(&[])
+    }
+
+    /// Returns whether the arguments to be returned by `Env::args()` have been set.
+    pub fn has_args(&self) -> bool {
+        self.args.is_some()
+    }
+
+    /// Returns whether the arguments to be returned by `Env::args_os()` have been set.
+    pub fn has_args_os(&self) -> bool {
+        self.args_os.is_some()
+    }
+
+    /// Returns whether the path to be returned by `Env::current_dir()` has been set.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use std::path::PathBuf;
+    /// use io_providers::{Env, SimulatedEnv};
+    ///
+    /// /// Gets the current directory, defaulting to "/" if it isn't known.
+    /// fn current_dir_or_root(env: &SimulatedEnv) -> PathBuf {
+    ///     if env.has_current_dir() {
+    ///         env.current_dir().unwrap()
+    ///     } else {
+    ///         PathBuf::from("/")
+    ///     }
+    /// }
+    ///
+    /// let mut env = SimulatedEnv::new();
+    /// assert_eq!(PathBuf::from("/"), current_dir_or_root(&env));
+    /// env.set_current_dir("/foo").unwrap();
+    /// assert_eq!(PathBuf::from("/foo"), current_dir_or_root(&env));
+    /// ```
+    pub fn has_current_dir(&self) -> bool {
+        self.current_dir.is_some()
+    }
+
+    /// Returns whether the path to be returned by `Env::current_exe()` has been set.
+    pub fn has_current_exe(&self) -> bool {
+        self.current_exe.is_some()
+    }
+
+    /// Returns whether the environment variable `key` has been set. As with `Env::var()`, the
+    /// name is compared case-insensitively if variable names are case-insensitive.
+    pub fn has_var<K: AsRef<ffi::OsStr>>(&self, key: K) -> bool {
+        self.vars.contains_key(&self.var_key(key.as_ref()))
     }
 
     /// Sets the path to be returned by `Env::home_dir()`.
@@ -59,6 +403,248 @@ impl SimulatedEnv {
     pub fn set_temp_dir<P: AsRef<Path>>(&mut self, path: P) {
         self.temp_dir = Some(PathBuf::from(path.as_ref()));
     }
+
+    /// Removes every environment variable whose name satisfies `predicate`, returning the number
+    /// of variables removed.
+    ///
+    /// The predicate is given each variable name with the casing used when it was set.
+    pub fn remove_vars_matching<F: Fn(&ffi::OsStr) -> bool>(&mut self, predicate: F) -> usize {
+        let count = self.vars.len();
+        self.vars.retain(|_, (k, _)| !predicate(k));
+        count - self.vars.len()
+    }
+
+    /// Removes every environment variable whose name starts with `prefix`, returning the number
+    /// of variables removed.
+    ///
+    /// If variable names are case-insensitive (see
+    /// [`set_case_insensitive_vars()`](#method.set_case_insensitive_vars)), so is the prefix.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use io_providers::{Env, SimulatedEnv};
+    ///
+    /// let mut env = SimulatedEnv::new();
+    /// env.set_var("MYAPP_HOME", "/app");
+    /// env.set_var("MYAPP_DEBUG", "1");
+    /// env.set_var("HOME", "/home/me");
+    ///
+    /// assert_eq!(2, env.remove_vars_with_prefix("MYAPP_"));
+    /// assert_eq!(1, env.vars().count());
+    /// ```
+    pub fn remove_vars_with_prefix(&mut self, prefix: &str) -> usize {
+        let prefix = self.var_key(ffi::OsStr::new(prefix));
+        let count = self.vars.len();
+        self.vars
+            .retain(|key, _| !key.as_encoded_bytes().starts_with(prefix.as_encoded_bytes()));
+        count - self.vars.len()
+    }
+
+    /// Sets the environment variable `k` to the value `v`, like
+    /// [`Env::set_var()`](trait.Env.html#tymethod.set_var), but returns an error of kind
+    /// `io::ErrorKind::InvalidInput` rather than panicking if the name is empty or contains `=` or
+    /// NUL, or if the value contains NUL.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use std::io;
+    /// use io_providers::SimulatedEnv;
+    ///
+    /// let mut env = SimulatedEnv::new();
+    /// assert!(env.try_set_var("FOO", "bar").is_ok());
+    /// assert_eq!(
+    ///     io::ErrorKind::InvalidInput,
+    ///     env.try_set_var("FOO=BAR", "baz").unwrap_err().kind());
+    /// ```
+    pub fn try_set_var<K: AsRef<ffi::OsStr>, V: AsRef<ffi::OsStr>>(
+        &mut self,
+        k: K,
+        v: V,
+    ) -> io::Result<()> {
+        let (k, v) = (k.as_ref(), v.as_ref());
+        let invalid = |message: String| Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        if k.is_empty() {
+            return invalid("environment variable name is empty".to_owned());
+        }
+        if k.as_encoded_bytes().iter().any(|&b| b == b'=' || b == 0) {
+            return invalid(format!(
+                "environment variable name {:?} contains '=' or NUL",
+                k
+            ));
+        }
+        if v.as_encoded_bytes().contains(&0) {
+            return invalid(format!(
+                "value of environment variable {:?} contains NUL",
+                k
+            ));
+        }
+
+        let key = self.var_key(k);
+        let value = v.to_os_string();
+        self.vars
+            .entry(key)
+            .and_modify(|entry| entry.1 = value.clone())
+            .or_insert_with(|| (k.to_os_string(), value));
+        Ok(())
+    }
+
+    /// Sets environment variables from the `KEY=value` lines of a `.env`-style file, read from the
+    /// `fs` provider.
+    ///
+    /// See [`load_dotenv_str()`](#method.load_dotenv_str) for the supported syntax. Returns an error
+    /// if the file can't be read, or if it isn't valid UTF-8.
+    pub fn load_dotenv<F: Fs, P: AsRef<Path>>(&mut self, fs: &F, path: P) -> io::Result<()> {
+        let contents = fs.read_to_string(path)?;
+        self.load_dotenv_str(&contents)
+    }
+
+    /// Sets environment variables from `contents`, which is in the format of a `.env` file.
+    ///
+    /// Each line has the form `KEY=value`, optionally preceded by `export `. Blank lines and lines
+    /// starting with `#` are ignored. Values may be unquoted (surrounding whitespace and a trailing
+    /// ` # comment` are removed), single-quoted (taken literally), or double-quoted (supporting the
+    /// escapes `\n`, `\r`, `\t`, `\\` and `\"`). Later lines override earlier ones, and
+    /// existing variables are overwritten.
+    ///
+    /// Returns an error of kind `io::ErrorKind::InvalidData` naming the line number if a line
+    /// can't be parsed, or of kind `io::ErrorKind::InvalidInput` if a name or value is invalid
+    /// (see [`try_set_var()`](#method.try_set_var)); no variables are set in either case.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use io_providers::{Env, SimulatedEnv};
+    ///
+    /// let mut env = SimulatedEnv::new();
+    /// env.load_dotenv_str("# Fixture\nNAME=\"Jane Doe\"\nDEBUG=0\nDEBUG=1\n").unwrap();
+    /// assert_eq!(Ok("Jane Doe".to_owned()), env.var("NAME"));
+    /// assert_eq!(Ok("1".to_owned()), env.var("DEBUG"));
+    /// ```
+    pub fn load_dotenv_str(&mut self, contents: &str) -> io::Result<()> {
+        let vars = dotenv::parse(contents)?;
+        let snapshot = self.vars.clone();
+        for (k, v) in vars {
+            if let Err(e) = self.try_set_var(k, v) {
+                self.vars = snapshot;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Captures the complete state of this environment (arguments, current directory and
+    /// executable, home and temp directories, and variables) so that it can later be restored
+    /// using [`restore()`](#method.restore).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use io_providers::{Env, SimulatedEnv};
+    ///
+    /// let mut env = SimulatedEnv::new();
+    /// env.set_var("FOO", "bar");
+    /// let snapshot = env.snapshot();
+    ///
+    /// env.set_var("FOO", "baz");
+    /// env.set_var("ABC", "123");
+    /// env.restore(snapshot);
+    ///
+    /// assert_eq!(Ok("bar".to_owned()), env.var("FOO"));
+    /// assert!(env.var("ABC").is_err());
+    /// ```
+    pub fn snapshot(&self) -> EnvSnapshot {
+        EnvSnapshot {
+            args: self.args.clone(),
+            args_os: self.args_os.clone(),
+            current_dir: self.current_dir.clone(),
+            current_exe: self.current_exe.clone(),
+            home_dir: self.home_dir.clone(),
+            temp_dir: self.temp_dir.clone(),
+            case_insensitive_vars: self.case_insensitive_vars,
+            platform: self.platform,
+            vars: self.vars.clone(),
+        }
+    }
+
+    /// Resets this environment to the state of a new `SimulatedEnv`, so that it can be reused.
+    ///
+    /// This clears the arguments, paths and variables which have been set, along with the
+    /// accesses recorded for [`check()`](#method.check) and [`var_reads()`](#method.var_reads).
+    /// Configuration is preserved: case-insensitivity of names, the platform, whether missing
+    /// values are deferred, and the callbacks registered using
+    /// [`on_current_dir_change()`](#method.on_current_dir_change) (which aren't invoked by the
+    /// reset).
+    pub fn reset(&mut self) {
+        self.args = None;
+        self.args_os = None;
+        self.current_dir = None;
+        self.current_exe = None;
+        self.home_dir = None;
+        self.temp_dir = None;
+        self.vars.clear();
+        self.missing_values.lock().unwrap().clear();
+        self.var_reads.lock().unwrap().clear();
+    }
+
+    /// Restores the state captured by [`snapshot()`](#method.snapshot), completely overwriting
+    /// the current state. Any variables set after the snapshot was taken are removed.
+    pub fn restore(&mut self, snapshot: EnvSnapshot) {
+        self.args = snapshot.args;
+        self.args_os = snapshot.args_os;
+        self.current_dir = snapshot.current_dir;
+        self.current_exe = snapshot.current_exe;
+        self.home_dir = snapshot.home_dir;
+        self.temp_dir = snapshot.temp_dir;
+        self.case_insensitive_vars = snapshot.case_insensitive_vars;
+        self.platform = snapshot.platform;
+        self.vars = snapshot.vars;
+    }
+
+    /// Looks up the variable `key`, recording the read for `var_reads()`.
+    fn read_var(&self, key: &ffi::OsStr) -> Option<ffi::OsString> {
+        let value = self.vars.get(&self.var_key(key)).map(|(_, v)| v.clone());
+        self.var_reads
+            .lock()
+            .unwrap()
+            .push((key.to_os_string(), value.is_some()));
+        value
+    }
+
+    /// Handles an access to a value which hasn't been set, either panicking or recording the
+    /// access and returning `placeholder`.
+    fn missing_value<T>(&self, getter: &str, placeholder: T) -> T {
+        if !self.defer_missing_values {
+            panic!("{} was called before a simulated value was set", getter);
+        }
+        let mut missing = self.missing_values.lock().unwrap();
+        if !missing.iter().any(|m| m == getter) {
+            missing.push(getter.to_owned());
+        }
+        placeholder
+    }
+
+    fn missing_path(&self, getter: &str) -> io::Result<PathBuf> {
+        let message = format!("{} was called before a simulated value was set", getter);
+        self.missing_value(
+            getter,
+            Err(io::Error::new(io::ErrorKind::NotFound, message)),
+        )
+    }
+
+    fn var_key(&self, k: &ffi::OsStr) -> ffi::OsString {
+        match k.to_str() {
+            Some(s) if self.case_insensitive_vars => ffi::OsString::from(s.to_uppercase()),
+            _ => k.to_os_string(),
+        }
+    }
+}
+
+impl Default for SimulatedEnv {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Env for SimulatedEnv {
@@ -68,31 +654,40 @@ impl Env for SimulatedEnv {
     type VarsOsIter = vec::IntoIter<(ffi::OsString, ffi::OsString)>;
 
     fn args(&self) -> Self::ArgsIter {
-        self.args
-            .clone()
-            .expect("Env::args() was called before a simulated value was set")
-            .into_iter()
+        self.args_ref().to_vec().into_iter()
     }
 
     fn args_os(&self) -> Self::ArgsOsIter {
-        self.args_os
-            .clone()
-            .expect("Env::args_os() was called before a simulated value was set")
-            .into_iter()
+        self.args_os_ref().to_vec().into_iter()
+    }
+
+    fn platform(&self) -> Platform {
+        self.platform
+    }
+
+    fn program_name(&self) -> Option<String> {
+        self.program_name_ref().map(str::to_owned)
+    }
+
+    fn args_after_program(&self) -> iter::Skip<Self::ArgsIter> {
+        // An empty placeholder (which doesn't allocate) is skipped instead of the program name, so
+        // that it isn't cloned only to be discarded
+        let args = iter::once(String::new()).chain(self.args_after_program_ref().iter().cloned());
+        args.collect::<Vec<_>>().into_iter().skip(1)
     }
 
     fn current_dir(&self) -> io::Result<PathBuf> {
-        Ok(self
-            .current_dir
-            .clone()
-            .expect("Env::current_dir() was called before a simulated value was set"))
+        match self.current_dir {
+            Some(ref path) => Ok(path.clone()),
+            None => self.missing_path("Env::current_dir()"),
+        }
     }
 
     fn current_exe(&self) -> io::Result<PathBuf> {
-        Ok(self
-            .current_exe
-            .clone()
-            .expect("Env::current_exe() was called before a simulated value was set"))
+        match self.current_exe {
+            Some(ref path) => Ok(path.clone()),
+            None => self.missing_path("Env::current_exe()"),
+        }
     }
 
     fn home_dir(&self) -> Option<PathBuf> {
@@ -100,40 +695,49 @@ impl Env for SimulatedEnv {
     }
 
     fn remove_var<K: AsRef<ffi::OsStr>>(&mut self, k: K) {
-        self.vars.remove(k.as_ref());
+        let key = self.var_key(k.as_ref());
+        self.vars.remove(&key);
     }
 
     fn set_current_dir<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
-        self.current_dir = Some(PathBuf::from(path.as_ref()));
+        let new = PathBuf::from(path.as_ref());
+        let old = self.current_dir.replace(new.clone());
+        for listener in self.current_dir_listeners.get_mut().unwrap() {
+            listener(old.as_deref(), &new);
+        }
         Ok(())
     }
 
+    /// Like [`std::env::set_var`](https://doc.rust-lang.org/std/env/fn.set_var.html), this panics
+    /// if the name is empty or contains `=` or NUL, or if the value contains NUL. Use
+    /// [`try_set_var()`](struct.SimulatedEnv.html#method.try_set_var) to handle these cases as
+    /// errors instead.
     fn set_var<K: AsRef<ffi::OsStr>, V: AsRef<ffi::OsStr>>(&mut self, k: K, v: V) {
-        let _ = self
-            .vars
-            .insert(k.as_ref().to_os_string(), v.as_ref().to_os_string());
+        if let Err(e) = self.try_set_var(k, v) {
+            panic!("Env::set_var() failed: {}", e);
+        }
     }
 
     fn temp_dir(&self) -> PathBuf {
-        self.temp_dir
-            .clone()
-            .expect("Env::temp_dir() was called before a simulated value was set")
+        match self.temp_dir {
+            Some(ref path) => path.clone(),
+            None => self.missing_value("Env::temp_dir()", PathBuf::new()),
+        }
     }
 
     fn var<K: AsRef<ffi::OsStr>>(&self, key: K) -> Result<String, env::VarError> {
-        self.vars
-            .get(&key.as_ref().to_os_string())
+        self.read_var(key.as_ref())
             .ok_or(env::VarError::NotPresent)
-            .and_then(|k| k.clone().into_string().map_err(env::VarError::NotUnicode))
+            .and_then(|v| v.into_string().map_err(env::VarError::NotUnicode))
     }
 
     fn var_os<K: AsRef<ffi::OsStr>>(&self, key: K) -> Option<ffi::OsString> {
-        self.vars.get(&key.as_ref().to_os_string()).cloned()
+        self.read_var(key.as_ref())
     }
 
     fn vars(&self) -> Self::VarsIter {
         self.vars
-            .iter()
+            .values()
             .map(|(k, v)| {
                 (
                     k.clone().into_string().unwrap(),
@@ -143,9 +747,13 @@ impl Env for SimulatedEnv {
             .into_iter()
     }
 
+    fn vars_filtered(&self, allow: &[&str]) -> vec::IntoIter<(String, String)> {
+        env_mod::filter_vars(self.vars_os(), allow, self.case_insensitive_vars)
+    }
+
     fn vars_os(&self) -> Self::VarsOsIter {
         self.vars
-            .iter()
+            .values()
             .map(|(k, v)| (k.clone(), v.clone()))
             .collect::<Vec<(ffi::OsString, ffi::OsString)>>()
             .into_iter()
@@ -157,10 +765,14 @@ impl Env for SimulatedEnv {
 mod tests {
     use std::env;
     use std::ffi::OsString;
-    use std::path::Path;
+    use std::io;
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
 
     use super::SimulatedEnv;
-    use env::Env;
+    use env::{Env, Platform};
+    use fs::{Fs, TempFs};
 
     #[test]
     #[should_panic]
@@ -180,6 +792,132 @@ mod tests {
         assert_eq!(args, result);
     }
 
+    #[test]
+    fn args_vec__moved_into_thread__same_args() {
+        let mut provider = SimulatedEnv::new();
+        let args = vec!["app".to_owned(), "-v".to_owned()];
+        let args_os = vec![OsString::from("app"), OsString::from("-v")];
+        provider.set_args(args.clone());
+        provider.set_args_os(args_os.clone());
+
+        let collected = provider.args_vec();
+        let collected_os = provider.args_os_vec();
+        let iter = provider.args();
+        let result = thread::spawn(move || (collected, collected_os, iter.collect::<Vec<_>>()))
+            .join()
+            .unwrap();
+
+        assert_eq!((args.clone(), args_os, args), result);
+    }
+
+    #[test]
+    fn args_ref__repeated_calls__borrow_without_cloning() {
+        let mut provider = SimulatedEnv::new();
+        provider.set_args(vec!["app".to_owned(); 10_000]);
+        provider.set_args_os(vec![OsString::from("app"); 10_000]);
+
+        let first = provider.args_ref().as_ptr();
+        let first_os = provider.args_os_ref().as_ptr();
+        for _ in 0..1_000 {
+            assert_eq!(first, provider.args_ref().as_ptr());
+            assert_eq!(first_os, provider.args_os_ref().as_ptr());
+        }
+        assert_eq!(10_000, provider.args_ref().len());
+    }
+
+    #[test]
+    #[should_panic]
+    fn args_ref__called_before_set__panics() {
+        let provider = SimulatedEnv::new();
+        let _ = provider.args_ref();
+    }
+
+    #[test]
+    fn args__set_empty__yields_nothing() {
+        let mut provider = SimulatedEnv::new();
+
+        provider.set_args(vec![]);
+        provider.set_args_os(vec![]);
+
+        assert!(provider.has_args());
+        assert_eq!(None, provider.args().next());
+        assert_eq!(None, provider.args_os().next());
+        assert!(provider.args_ref().is_empty());
+    }
+
+    #[test]
+    fn program_name__args_set__first_arg_separated() {
+        let mut provider = SimulatedEnv::new();
+
+        provider.set_args(vec!["app".to_owned(), "-v".to_owned(), "file".to_owned()]);
+
+        assert_eq!(Some("app".to_owned()), provider.program_name());
+        assert_eq!(
+            vec!["-v".to_owned(), "file".to_owned()],
+            provider.args_after_program().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn program_name__args_set_empty__none() {
+        let mut provider = SimulatedEnv::new();
+
+        provider.set_args(vec![]);
+
+        assert_eq!(None, provider.program_name());
+        assert_eq!(None, provider.args_after_program().next());
+        assert_eq!(None, provider.program_name_ref());
+        assert!(provider.args_after_program_ref().is_empty());
+    }
+
+    #[test]
+    fn program_name_ref__args_set__borrowed_from_args() {
+        let mut provider = SimulatedEnv::new();
+
+        provider.set_args(vec!["app".to_owned(), "-v".to_owned()]);
+
+        assert_eq!(
+            provider.args_ref()[0].as_ptr(),
+            provider.program_name_ref().unwrap().as_ptr()
+        );
+        assert_eq!(&provider.args_ref()[1..], provider.args_after_program_ref());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn set_current_exe_resolved__path_is_symlink__target_returned() {
+        let mut fs = TempFs::new().unwrap();
+        fs.create_dir_all("/opt/app-1.2/bin").unwrap();
+        fs.create_dir_all("/usr/bin").unwrap();
+        fs.write("/opt/app-1.2/bin/app", "").unwrap();
+        ::std::os::unix::fs::symlink("../../opt/app-1.2/bin/app", fs.path().join("usr/bin/app"))
+            .unwrap();
+        let mut provider = SimulatedEnv::new();
+
+        provider
+            .set_current_exe_resolved(&fs, "/usr/bin/app")
+            .unwrap();
+        assert_eq!(
+            Path::new("/opt/app-1.2/bin/app"),
+            provider.current_exe().unwrap()
+        );
+
+        provider.set_current_exe("/usr/bin/app");
+        assert_eq!(Path::new("/usr/bin/app"), provider.current_exe().unwrap());
+    }
+
+    #[test]
+    fn set_current_exe_resolved__missing_path__error_and_unchanged() {
+        let fs = TempFs::new().unwrap();
+        let mut provider = SimulatedEnv::new();
+        provider.set_current_exe("/bin/old");
+
+        let result = provider.set_current_exe_resolved(&fs, "/bin/missing");
+
+        assert_eq!(io::ErrorKind::NotFound, result.unwrap_err().kind());
+        assert_eq!(Path::new("/bin/old"), provider.current_exe().unwrap());
+    }
+
     #[test]
     #[should_panic]
     fn args_os__called_before_set__panics() {
@@ -209,6 +947,142 @@ mod tests {
         let _ = provider.current_dir();
     }
 
+    #[test]
+    fn absolute_path__absolute_path__returned_unchanged() {
+        let mut provider = SimulatedEnv::new();
+        provider.set_current_dir("/foo/bar").unwrap();
+        let path = if cfg!(windows) {
+            Path::new("C:\\a\\..\\b")
+        } else {
+            Path::new("/a/../b")
+        };
+
+        let result = provider.absolute_path(path).unwrap();
+
+        assert_eq!(path.as_os_str(), result.as_os_str());
+    }
+
+    #[test]
+    fn absolute_path__relative_path_with_parent_dirs__joined_and_normalized() {
+        let mut provider = SimulatedEnv::new();
+        provider.set_current_dir("/foo/bar").unwrap();
+
+        let result = provider.absolute_path("../baz/./qux/../a.txt").unwrap();
+
+        assert_eq!(Path::new("/foo/baz/a.txt"), result);
+    }
+
+    #[test]
+    fn check__three_missing_values_accessed__lists_all_three() {
+        let mut provider = SimulatedEnv::new();
+        provider.set_defer_missing_values(true);
+
+        assert_eq!(0, provider.args().count());
+        assert_eq!(
+            io::ErrorKind::NotFound,
+            provider.current_exe().unwrap_err().kind()
+        );
+        assert_eq!(PathBuf::new(), provider.temp_dir());
+        let _ = provider.args();
+
+        assert_eq!(
+            Err(vec![
+                "Env::args()".to_owned(),
+                "Env::current_exe()".to_owned(),
+                "Env::temp_dir()".to_owned(),
+            ]),
+            provider.check()
+        );
+    }
+
+    #[test]
+    fn check__no_missing_values_accessed__ok() {
+        let mut provider = SimulatedEnv::new();
+        provider.set_defer_missing_values(true);
+        provider.set_temp_dir("/tmp");
+
+        let _ = provider.temp_dir();
+
+        assert_eq!(Ok(()), provider.check());
+    }
+
+    #[test]
+    fn has_current_dir__before_and_after_set__false_then_true() {
+        let mut provider = SimulatedEnv::new();
+
+        assert!(!provider.has_current_dir());
+        provider.set_current_dir("/foo").unwrap();
+        assert!(provider.has_current_dir());
+        assert!(!provider.has_current_exe());
+    }
+
+    #[test]
+    fn has_args_and_has_var__before_and_after_set__false_then_true() {
+        let mut provider = SimulatedEnv::new();
+        provider.set_case_insensitive_vars(true);
+
+        assert!(!provider.has_args());
+        assert!(!provider.has_var("FOO"));
+        provider.set_args(vec![]);
+        provider.set_var("Foo", "bar");
+        assert!(provider.has_args());
+        assert!(!provider.has_args_os());
+        assert!(provider.has_var("FOO"));
+    }
+
+    #[test]
+    fn on_current_dir_change__set_current_dir__invoked_with_old_and_new() {
+        let mut provider = SimulatedEnv::new();
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let recorder = changes.clone();
+        provider.on_current_dir_change(move |old, new| {
+            recorder
+                .lock()
+                .unwrap()
+                .push((old.map(|p| p.to_path_buf()), new.to_path_buf()));
+        });
+
+        provider.set_current_dir("/foo").unwrap();
+        provider.set_current_dir("/foo/bar").unwrap();
+
+        assert_eq!(
+            vec![
+                (None, PathBuf::from("/foo")),
+                (Some(PathBuf::from("/foo")), PathBuf::from("/foo/bar")),
+            ],
+            *changes.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn load_dotenv__fixture_file__vars_set_with_later_lines_overriding() {
+        let mut fs = TempFs::new().unwrap();
+        fs.write(
+            ".env",
+            "# Database settings\nDB_HOST=localhost\nDB_NAME='app db'\n\nDB_HOST=\"db.internal\"\n",
+        )
+        .unwrap();
+        let mut provider = SimulatedEnv::new();
+        provider.set_var("DB_PORT", "5432");
+
+        provider.load_dotenv(&fs, ".env").unwrap();
+
+        assert_eq!(Ok("db.internal".to_owned()), provider.var("DB_HOST"));
+        assert_eq!(Ok("app db".to_owned()), provider.var("DB_NAME"));
+        assert_eq!(Ok("5432".to_owned()), provider.var("DB_PORT"));
+        assert_eq!(3, provider.vars().count());
+    }
+
+    #[test]
+    fn load_dotenv_str__invalid_name__no_vars_set() {
+        let mut provider = SimulatedEnv::new();
+
+        let result = provider.load_dotenv_str("A=1\nB\u{0}=2\n");
+
+        assert_eq!(io::ErrorKind::InvalidInput, result.unwrap_err().kind());
+        assert_eq!(0, provider.vars().count());
+    }
+
     #[test]
     fn current_dir__set_and_get__success() {
         let mut provider = SimulatedEnv::new();
@@ -233,7 +1107,7 @@ mod tests {
         let path = Path::new("/foo/bar");
 
         provider.set_current_exe(path);
-        let result = provider.current_dir().unwrap();
+        let result = provider.current_exe().unwrap();
 
         assert_eq!(path, result.as_path());
     }
@@ -296,6 +1170,184 @@ mod tests {
         assert_eq!(Ok("bar".to_owned()), result);
     }
 
+    #[test]
+    fn platform__not_set__compiled_platform() {
+        let provider = SimulatedEnv::new();
+
+        assert_eq!(Platform::current(), provider.platform());
+        assert_eq!(cfg!(windows), provider.platform() == Platform::Windows);
+    }
+
+    #[test]
+    fn platform__set_windows__windows_separator_used() {
+        fn config_path<E: Env>(env: &E) -> String {
+            match env.platform() {
+                Platform::Windows => "%APPDATA%\\app".to_owned(),
+                _ => "~/.config/app".to_owned(),
+            }
+        }
+        let mut provider = SimulatedEnv::new();
+
+        provider.set_platform(Platform::Windows);
+        assert_eq!("%APPDATA%\\app", config_path(&provider));
+        assert_eq!('\\', provider.platform().path_separator());
+
+        provider.set_platform(Platform::Unix);
+        assert_eq!("~/.config/app", config_path(&provider));
+        assert_eq!('/', provider.platform().path_separator());
+    }
+
+    #[test]
+    fn var_reads__two_vars_read__logged_in_order() {
+        let mut provider = SimulatedEnv::new();
+        provider.set_var("API_URL", "https://example.com");
+        provider.set_var("AWS_SECRET_ACCESS_KEY", "secret");
+
+        let _ = provider.var_os("API_URL");
+        let _ = provider.var("TIMEOUT");
+
+        assert_eq!(
+            vec![
+                (OsString::from("API_URL"), true),
+                (OsString::from("TIMEOUT"), false)
+            ],
+            provider.var_reads()
+        );
+        provider.clear_var_reads();
+        assert!(provider.var_reads().is_empty());
+    }
+
+    #[test]
+    fn vars__set_out_of_order__sorted_by_name() {
+        let mut provider = SimulatedEnv::new();
+        provider.set_case_insensitive_vars(false);
+        for key in &["PATH", "HOME", "TERM", "EDITOR", "LANG"] {
+            provider.set_var(key, "value");
+        }
+
+        let keys: Vec<String> = provider.vars().map(|(k, _)| k).collect();
+        let os_keys: Vec<OsString> = provider.vars_os().map(|(k, _)| k).collect();
+
+        assert_eq!(vec!["EDITOR", "HOME", "LANG", "PATH", "TERM"], keys);
+        assert_eq!(
+            keys.into_iter().map(OsString::from).collect::<Vec<_>>(),
+            os_keys
+        );
+    }
+
+    #[test]
+    fn set_case_insensitive_vars__names_differ_only_in_case__first_kept_and_others_returned() {
+        let mut provider = SimulatedEnv::new();
+        provider.set_case_insensitive_vars(false);
+        provider.set_var("Path", "lower");
+        provider.set_var("PATH", "upper");
+        provider.set_var("HOME", "home");
+
+        let collisions = provider.set_case_insensitive_vars(true);
+
+        assert_eq!(
+            vec![(OsString::from("Path"), OsString::from("lower"))],
+            collisions
+        );
+        assert_eq!("upper", provider.var("path").unwrap());
+        assert_eq!("home", provider.var("HOME").unwrap());
+        assert!(provider.set_case_insensitive_vars(false).is_empty());
+    }
+
+    #[test]
+    fn vars__case_insensitive__sorted_ignoring_case() {
+        let mut provider = SimulatedEnv::new();
+        provider.set_case_insensitive_vars(true);
+        provider.set_var("b", "1");
+        provider.set_var("A", "2");
+        provider.set_var("c", "3");
+
+        let keys: Vec<String> = provider.vars().map(|(k, _)| k).collect();
+
+        assert_eq!(vec!["A", "b", "c"], keys);
+    }
+
+    #[test]
+    fn vars_filtered__five_vars_two_allowed__only_allowed_returned() {
+        let mut provider = SimulatedEnv::new();
+        provider.set_case_insensitive_vars(false);
+        provider.set_var("PATH", "/usr/bin");
+        provider.set_var("HOME", "/home/user");
+        provider.set_var("AWS_SECRET_ACCESS_KEY", "secret");
+        provider.set_var("API_TOKEN", "token");
+        provider.set_var("LANG", "en_US.UTF-8");
+
+        let vars: Vec<(String, String)> = provider
+            .vars_filtered(&["PATH", "LANG", "TERM", "home"])
+            .collect();
+
+        assert_eq!(
+            vec![
+                ("LANG".to_owned(), "en_US.UTF-8".to_owned()),
+                ("PATH".to_owned(), "/usr/bin".to_owned())
+            ],
+            vars
+        );
+    }
+
+    #[test]
+    fn vars_filtered__case_insensitive__case_ignored() {
+        let mut provider = SimulatedEnv::new();
+        provider.set_case_insensitive_vars(true);
+        provider.set_var("Path", "C:\\Windows");
+
+        let vars: Vec<(String, String)> = provider.vars_filtered(&["PATH"]).collect();
+
+        assert_eq!(vec![("Path".to_owned(), "C:\\Windows".to_owned())], vars);
+    }
+
+    #[test]
+    fn vars_filtered__case_insensitive_non_ascii__folded_like_lookup() {
+        let mut provider = SimulatedEnv::new();
+        provider.set_case_insensitive_vars(true);
+        provider.set_var("ÉTAT", "prêt");
+
+        let vars: Vec<(String, String)> = provider.vars_filtered(&["état"]).collect();
+
+        assert_eq!(Ok("prêt".to_owned()), provider.var("état"));
+        assert_eq!(vec![("ÉTAT".to_owned(), "prêt".to_owned())], vars);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn vars_filtered__non_unicode_value_not_allowed__ignored() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let mut provider = SimulatedEnv::new();
+        provider.set_var("PATH", "/usr/bin");
+        provider.set_var("BINARY", OsString::from_vec(vec![b'a', 0xff, b'b']));
+
+        let vars: Vec<(String, String)> = provider.vars_filtered(&["PATH"]).collect();
+
+        assert_eq!(vec![("PATH".to_owned(), "/usr/bin".to_owned())], vars);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn var__non_unicode_value__not_unicode_error_and_raw_var_os() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let mut provider = SimulatedEnv::new();
+        let value = OsString::from_vec(vec![b'a', 0xff, b'b']);
+
+        provider.set_var("FOO", &value);
+
+        assert_eq!(
+            Err(env::VarError::NotUnicode(value.clone())),
+            provider.var("FOO")
+        );
+        assert_eq!(Some(value.clone()), provider.var_os("FOO"));
+        assert_eq!(
+            vec![(OsString::from("FOO"), value)],
+            provider.vars_os().collect::<Vec<_>>()
+        );
+    }
+
     #[test]
     fn var_os__get_undefined_var__returns_none() {
         let provider = SimulatedEnv::new();
@@ -357,4 +1409,164 @@ mod tests {
             OsString::from("123".to_owned())
         )));
     }
+
+    #[test]
+    #[cfg(windows)]
+    fn var__different_case_on_windows__returns_value() {
+        let mut provider = SimulatedEnv::new();
+        provider.set_var("PATH", "C:\\bin");
+
+        let result = provider.var("path");
+
+        assert_eq!(Ok("C:\\bin".to_owned()), result);
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn var__different_case_on_unix__returns_not_present() {
+        let mut provider = SimulatedEnv::new();
+        provider.set_var("PATH", "/bin");
+
+        let result = provider.var("path");
+
+        assert_eq!(Err(env::VarError::NotPresent), result);
+    }
+
+    #[test]
+    fn set_var__case_insensitive_mode__replaces_value_and_preserves_casing() {
+        let mut provider = SimulatedEnv::new();
+        provider.set_case_insensitive_vars(true);
+        provider.set_var("Path", "/bin");
+
+        provider.set_var("PATH", "/usr/bin");
+        let result: Vec<(String, String)> = provider.vars().collect();
+
+        assert_eq!(Ok("/usr/bin".to_owned()), provider.var("path"));
+        assert_eq!(vec![("Path".to_owned(), "/usr/bin".to_owned())], result);
+    }
+
+    #[test]
+    fn remove_var__case_insensitive_mode__value_is_removed() {
+        let mut provider = SimulatedEnv::new();
+        provider.set_case_insensitive_vars(true);
+        provider.set_var("Path", "/bin");
+
+        provider.remove_var("PATH");
+
+        assert_eq!(None, provider.var_os("Path"));
+    }
+
+    #[test]
+    fn restore__vars_added_after_snapshot__vars_are_removed() {
+        let mut provider = SimulatedEnv::new();
+        provider.set_var("FOO", "bar");
+        let snapshot = provider.snapshot();
+
+        provider.set_var("FOO", "baz");
+        provider.set_var("ABC", "123");
+        provider.restore(snapshot);
+
+        assert_eq!(Ok("bar".to_owned()), provider.var("FOO"));
+        assert_eq!(Err(env::VarError::NotPresent), provider.var("ABC"));
+        assert_eq!(1, provider.vars().count());
+    }
+
+    #[test]
+    fn restore__dirs_changed_after_snapshot__dirs_are_restored() {
+        let mut provider = SimulatedEnv::new();
+        provider.set_current_dir("/foo").unwrap();
+        provider.set_args(vec!["app".to_owned()]);
+        let snapshot = provider.snapshot();
+
+        provider.set_current_dir("/bar").unwrap();
+        provider.set_args(vec!["other".to_owned()]);
+        provider.set_temp_dir("/tmp");
+        provider.restore(snapshot);
+
+        assert_eq!(Path::new("/foo"), provider.current_dir().unwrap().as_path());
+        assert_eq!(vec!["app".to_owned()], provider.args().collect::<Vec<String>>());
+        assert_eq!(None, provider.snapshot().temp_dir);
+    }
+
+    #[test]
+    fn remove_vars_with_prefix__some_vars_match__only_survivors_remain() {
+        let mut provider = SimulatedEnv::new();
+        provider.set_var("MYAPP_HOME", "/app");
+        provider.set_var("MYAPP_DEBUG", "1");
+        provider.set_var("MYAPP_LOG", "trace");
+        provider.set_var("HOME", "/home/me");
+        provider.set_var("APP_MYAPP_X", "x");
+
+        let result = provider.remove_vars_with_prefix("MYAPP_");
+        let mut survivors: Vec<String> = provider.vars().map(|(k, _)| k).collect();
+        survivors.sort();
+
+        assert_eq!(3, result);
+        assert_eq!(vec!["APP_MYAPP_X".to_owned(), "HOME".to_owned()], survivors);
+    }
+
+    #[test]
+    fn remove_vars_matching__predicate_matches__returns_count_removed() {
+        let mut provider = SimulatedEnv::new();
+        provider.set_var("FOO", "1");
+        provider.set_var("BAR", "2");
+        provider.set_var("BAZ", "3");
+
+        let result = provider.remove_vars_matching(|k| k.to_str().unwrap().starts_with("BA"));
+
+        assert_eq!(2, result);
+        assert_eq!(Ok("1".to_owned()), provider.var("FOO"));
+        assert_eq!(1, provider.vars().count());
+    }
+
+    #[test]
+    fn try_set_var__empty_key__returns_invalid_input() {
+        let mut provider = SimulatedEnv::new();
+
+        let result = provider.try_set_var("", "bar");
+
+        assert_eq!(io::ErrorKind::InvalidInput, result.unwrap_err().kind());
+        assert_eq!(0, provider.vars().count());
+    }
+
+    #[test]
+    fn try_set_var__key_contains_equals__returns_invalid_input() {
+        let mut provider = SimulatedEnv::new();
+
+        let result = provider.try_set_var("FOO=BAR", "baz");
+
+        assert_eq!(io::ErrorKind::InvalidInput, result.unwrap_err().kind());
+        assert_eq!(0, provider.vars().count());
+    }
+
+    #[test]
+    fn try_set_var__value_contains_nul__returns_invalid_input() {
+        let mut provider = SimulatedEnv::new();
+
+        let result = provider.try_set_var("FOO", "b\0ar");
+
+        assert_eq!(io::ErrorKind::InvalidInput, result.unwrap_err().kind());
+        assert_eq!(0, provider.vars().count());
+    }
+
+    #[test]
+    #[should_panic]
+    fn set_var__empty_key__panics() {
+        let mut provider = SimulatedEnv::new();
+        provider.set_var("", "bar");
+    }
+
+    #[test]
+    #[should_panic]
+    fn set_var__key_contains_equals__panics() {
+        let mut provider = SimulatedEnv::new();
+        provider.set_var("FOO=BAR", "baz");
+    }
+
+    #[test]
+    #[should_panic]
+    fn set_var__value_contains_nul__panics() {
+        let mut provider = SimulatedEnv::new();
+        provider.set_var("FOO", "b\0ar");
+    }
 }
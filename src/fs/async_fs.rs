@@ -0,0 +1,309 @@
+use std::fs;
+use std::future::{self, Future};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio;
+
+use fs::OpenOptions;
+
+/// The future type returned by [`AsyncFs`](trait.AsyncFs.html) operations.
+///
+/// Futures are boxed (and don't borrow the provider) so that `AsyncFs` can be implemented without
+/// `async fn` support in traits.
+pub type FsFuture<T> = Pin<Box<dyn Future<Output = io::Result<T>> + Send>>;
+
+/// Wraps an already-known result in an [`FsFuture`](type.FsFuture.html).
+pub(crate) fn ready<T: Send + 'static>(result: io::Result<T>) -> FsFuture<T> {
+    Box::pin(future::ready(result))
+}
+
+/// Provides asynchronous access to file I/O.
+///
+/// This mirrors [`Fs`](trait.Fs.html), so that fixture setup can be shared between synchronous
+/// and asynchronous code. It is only available when the `async` feature is enabled.
+///
+/// Since providers such as `TempFs` implement both traits, calls must be fully qualified (e.g.
+/// `AsyncFs::read(&fs, path)`) when `Fs` and `AsyncFs` are both in scope.
+///
+/// # Examples
+///
+/// ```
+/// extern crate io_providers;
+/// extern crate tokio;
+///
+/// use io_providers::fs::{AsyncFs, TempFs};
+///
+/// fn main() {
+///     let mut fs = TempFs::new().unwrap();
+///     let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+///
+///     runtime.block_on(AsyncFs::write(&mut fs, "test.txt", "contents")).unwrap();
+///     let contents = runtime.block_on(AsyncFs::read_to_string(&fs, "test.txt")).unwrap();
+///
+///     assert_eq!("contents", contents);
+/// }
+/// ```
+pub trait AsyncFs {
+    /// Opens a file at `path` with the options specified by `open_options`.
+    ///
+    /// See [tokio::fs::OpenOptions](https://docs.rs/tokio/1/tokio/fs/struct.OpenOptions.html#method.open)
+    /// for more information.
+    fn open<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        open_options: &OpenOptions,
+    ) -> FsFuture<tokio::fs::File>;
+
+    /// Copies the contents of one file to another.
+    ///
+    /// See [tokio::fs::copy](https://docs.rs/tokio/1/tokio/fs/fn.copy.html) for more information.
+    fn copy<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, from: P, to: Q) -> FsFuture<u64>;
+
+    /// Creates a new, empty directory at the provided path.
+    ///
+    /// See [tokio::fs::create_dir](https://docs.rs/tokio/1/tokio/fs/fn.create_dir.html) for more
+    /// information.
+    fn create_dir<P: AsRef<Path>>(&mut self, path: P) -> FsFuture<()>;
+
+    /// Recursively create a directory and all of its parent components if they are missing.
+    ///
+    /// See [tokio::fs::create_dir_all](https://docs.rs/tokio/1/tokio/fs/fn.create_dir_all.html)
+    /// for more information.
+    fn create_dir_all<P: AsRef<Path>>(&mut self, path: P) -> FsFuture<()>;
+
+    /// Creates a new hard link on the filesystem.
+    ///
+    /// See [tokio::fs::hard_link](https://docs.rs/tokio/1/tokio/fs/fn.hard_link.html) for more
+    /// information.
+    fn hard_link<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, src: P, dst: Q) -> FsFuture<()>;
+
+    /// Given a path, queries the file system to get information about a file, directory, etc.
+    ///
+    /// See [tokio::fs::metadata](https://docs.rs/tokio/1/tokio/fs/fn.metadata.html) for more
+    /// information.
+    fn metadata<P: AsRef<Path>>(&self, path: P) -> FsFuture<fs::Metadata>;
+
+    /// Reads the entire contents of a file into a bytes vector.
+    ///
+    /// See [tokio::fs::read](https://docs.rs/tokio/1/tokio/fs/fn.read.html) for more information.
+    fn read<P: AsRef<Path>>(&self, path: P) -> FsFuture<Vec<u8>>;
+
+    /// Returns a stream over the entries within a directory.
+    ///
+    /// See [tokio::fs::read_dir](https://docs.rs/tokio/1/tokio/fs/fn.read_dir.html) for more
+    /// information.
+    fn read_dir<P: AsRef<Path>>(&self, path: P) -> FsFuture<tokio::fs::ReadDir>;
+
+    /// Reads a symbolic link, returning the file that the link points to.
+    ///
+    /// See [tokio::fs::read_link](https://docs.rs/tokio/1/tokio/fs/fn.read_link.html) for more
+    /// information.
+    fn read_link<P: AsRef<Path>>(&self, path: P) -> FsFuture<PathBuf>;
+
+    /// Reads the entire contents of a file into a string.
+    ///
+    /// See [tokio::fs::read_to_string](https://docs.rs/tokio/1/tokio/fs/fn.read_to_string.html)
+    /// for more information.
+    fn read_to_string<P: AsRef<Path>>(&self, path: P) -> FsFuture<String>;
+
+    /// Removes an existing, empty directory.
+    ///
+    /// See [tokio::fs::remove_dir](https://docs.rs/tokio/1/tokio/fs/fn.remove_dir.html) for more
+    /// information.
+    fn remove_dir<P: AsRef<Path>>(&mut self, path: P) -> FsFuture<()>;
+
+    /// Removes a directory at this path, after removing all its contents.
+    ///
+    /// See [tokio::fs::remove_dir_all](https://docs.rs/tokio/1/tokio/fs/fn.remove_dir_all.html)
+    /// for more information.
+    fn remove_dir_all<P: AsRef<Path>>(&mut self, path: P) -> FsFuture<()>;
+
+    /// Removes a file from the filesystem.
+    ///
+    /// See [tokio::fs::remove_file](https://docs.rs/tokio/1/tokio/fs/fn.remove_file.html) for
+    /// more information.
+    fn remove_file<P: AsRef<Path>>(&mut self, path: P) -> FsFuture<()>;
+
+    /// Renames a file or directory to a new name, replacing the original file if `to` already
+    /// exists.
+    ///
+    /// See [tokio::fs::rename](https://docs.rs/tokio/1/tokio/fs/fn.rename.html) for more
+    /// information.
+    fn rename<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, from: P, to: Q) -> FsFuture<()>;
+
+    /// Changes the permissions found on a file or a directory.
+    ///
+    /// See [tokio::fs::set_permissions](https://docs.rs/tokio/1/tokio/fs/fn.set_permissions.html)
+    /// for more information.
+    fn set_permissions<P: AsRef<Path>>(&mut self, path: P, perm: fs::Permissions) -> FsFuture<()>;
+
+    /// Queries the metadata about a file without following symlinks.
+    ///
+    /// See [tokio::fs::symlink_metadata](https://docs.rs/tokio/1/tokio/fs/fn.symlink_metadata.html)
+    /// for more information.
+    fn symlink_metadata<P: AsRef<Path>>(&self, path: P) -> FsFuture<fs::Metadata>;
+
+    /// Writes a slice as the entire contents of a file.
+    ///
+    /// See [tokio::fs::write](https://docs.rs/tokio/1/tokio/fs/fn.write.html) for more
+    /// information.
+    fn write<P: AsRef<Path>, C: AsRef<[u8]>>(&mut self, path: P, contents: C) -> FsFuture<()>;
+
+    /// Returns whether the path points at an existing entity.
+    ///
+    /// Unlike [`Fs::exists()`](trait.Fs.html#tymethod.exists), errors encountered while checking
+    /// are returned rather than treated as `false`.
+    ///
+    /// See [tokio::fs::try_exists](https://docs.rs/tokio/1/tokio/fs/fn.try_exists.html) for more
+    /// information.
+    fn exists<P: AsRef<Path>>(&self, path: P) -> FsFuture<bool>;
+}
+
+/// Provides asynchronous access to native file I/O, using
+/// [`tokio::fs`](https://docs.rs/tokio/1/tokio/fs/).
+///
+/// Like `tokio::fs` itself, the returned futures must be run within a Tokio runtime.
+#[derive(Debug, Default)]
+pub struct TokioFs;
+
+impl AsyncFs for TokioFs {
+    fn open<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        open_options: &OpenOptions,
+    ) -> FsFuture<tokio::fs::File> {
+        ::check_real_io_allowed("TokioFs");
+        Box::pin(OpenFuture::new(open_options.as_std(), owned(path)))
+    }
+
+    fn copy<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, from: P, to: Q) -> FsFuture<u64> {
+        ::check_real_io_allowed("TokioFs");
+        Box::pin(tokio::fs::copy(owned(from), owned(to)))
+    }
+
+    fn create_dir<P: AsRef<Path>>(&mut self, path: P) -> FsFuture<()> {
+        ::check_real_io_allowed("TokioFs");
+        Box::pin(tokio::fs::create_dir(owned(path)))
+    }
+
+    fn create_dir_all<P: AsRef<Path>>(&mut self, path: P) -> FsFuture<()> {
+        ::check_real_io_allowed("TokioFs");
+        Box::pin(tokio::fs::create_dir_all(owned(path)))
+    }
+
+    fn hard_link<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, src: P, dst: Q) -> FsFuture<()> {
+        ::check_real_io_allowed("TokioFs");
+        Box::pin(tokio::fs::hard_link(owned(src), owned(dst)))
+    }
+
+    fn metadata<P: AsRef<Path>>(&self, path: P) -> FsFuture<fs::Metadata> {
+        ::check_real_io_allowed("TokioFs");
+        Box::pin(tokio::fs::metadata(owned(path)))
+    }
+
+    fn read<P: AsRef<Path>>(&self, path: P) -> FsFuture<Vec<u8>> {
+        ::check_real_io_allowed("TokioFs");
+        Box::pin(tokio::fs::read(owned(path)))
+    }
+
+    fn read_dir<P: AsRef<Path>>(&self, path: P) -> FsFuture<tokio::fs::ReadDir> {
+        ::check_real_io_allowed("TokioFs");
+        Box::pin(tokio::fs::read_dir(owned(path)))
+    }
+
+    fn read_link<P: AsRef<Path>>(&self, path: P) -> FsFuture<PathBuf> {
+        ::check_real_io_allowed("TokioFs");
+        Box::pin(tokio::fs::read_link(owned(path)))
+    }
+
+    fn read_to_string<P: AsRef<Path>>(&self, path: P) -> FsFuture<String> {
+        ::check_real_io_allowed("TokioFs");
+        Box::pin(tokio::fs::read_to_string(owned(path)))
+    }
+
+    fn remove_dir<P: AsRef<Path>>(&mut self, path: P) -> FsFuture<()> {
+        ::check_real_io_allowed("TokioFs");
+        Box::pin(tokio::fs::remove_dir(owned(path)))
+    }
+
+    fn remove_dir_all<P: AsRef<Path>>(&mut self, path: P) -> FsFuture<()> {
+        ::check_real_io_allowed("TokioFs");
+        Box::pin(tokio::fs::remove_dir_all(owned(path)))
+    }
+
+    fn remove_file<P: AsRef<Path>>(&mut self, path: P) -> FsFuture<()> {
+        ::check_real_io_allowed("TokioFs");
+        Box::pin(tokio::fs::remove_file(owned(path)))
+    }
+
+    fn rename<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, from: P, to: Q) -> FsFuture<()> {
+        ::check_real_io_allowed("TokioFs");
+        Box::pin(tokio::fs::rename(owned(from), owned(to)))
+    }
+
+    fn set_permissions<P: AsRef<Path>>(&mut self, path: P, perm: fs::Permissions) -> FsFuture<()> {
+        ::check_real_io_allowed("TokioFs");
+        Box::pin(tokio::fs::set_permissions(owned(path), perm))
+    }
+
+    fn symlink_metadata<P: AsRef<Path>>(&self, path: P) -> FsFuture<fs::Metadata> {
+        ::check_real_io_allowed("TokioFs");
+        Box::pin(tokio::fs::symlink_metadata(owned(path)))
+    }
+
+    fn write<P: AsRef<Path>, C: AsRef<[u8]>>(&mut self, path: P, contents: C) -> FsFuture<()> {
+        ::check_real_io_allowed("TokioFs");
+        Box::pin(tokio::fs::write(owned(path), contents.as_ref().to_vec()))
+    }
+
+    fn exists<P: AsRef<Path>>(&self, path: P) -> FsFuture<bool> {
+        ::check_real_io_allowed("TokioFs");
+        Box::pin(tokio::fs::try_exists(owned(path)))
+    }
+}
+
+fn owned<P: AsRef<Path>>(path: P) -> PathBuf {
+    path.as_ref().to_path_buf()
+}
+
+/// A future which opens a file on Tokio's blocking thread pool.
+///
+/// `tokio::fs::OpenOptions::open()` borrows its options, so its future can't be returned from
+/// `AsyncFs::open()`. Instead, this future owns the options and spawns the blocking open the first
+/// time it is polled (i.e. from within the runtime).
+pub(crate) struct OpenFuture {
+    open_options: fs::OpenOptions,
+    path: PathBuf,
+    handle: Option<tokio::task::JoinHandle<io::Result<fs::File>>>,
+}
+
+impl OpenFuture {
+    pub(crate) fn new(open_options: fs::OpenOptions, path: PathBuf) -> OpenFuture {
+        OpenFuture {
+            open_options,
+            path,
+            handle: None,
+        }
+    }
+}
+
+impl Future for OpenFuture {
+    type Output = io::Result<tokio::fs::File>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        ::check_real_io_allowed("TokioFs");
+        if self.handle.is_none() {
+            let open_options = self.open_options.clone();
+            let path = self.path.clone();
+            self.handle = Some(tokio::task::spawn_blocking(move || open_options.open(path)));
+        }
+        match Pin::new(self.handle.as_mut().unwrap()).poll(cx) {
+            Poll::Ready(Ok(result)) => Poll::Ready(result.map(tokio::fs::File::from_std)),
+            Poll::Ready(Err(e)) => Poll::Ready(Err(io::Error::other(e))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
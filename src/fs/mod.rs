@@ -1,14 +1,43 @@
 //! Defines traits and implementations for filesystem manipulation operations.
 
+#[cfg(feature = "async")]
+mod async_fs;
 mod native;
 mod temp;
 
+use std::ffi::OsString;
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
 
+#[cfg(feature = "async")]
+pub use self::async_fs::{AsyncFs, FsFuture, TokioFs};
 pub use self::native::NativeFs;
-pub use self::temp::TempFs;
+pub use self::temp::{NormalizationPolicy, ReadTransformFn, TempFs};
+
+/// Extension methods for the directory entries returned by `read_dir()`.
+///
+/// Entries expose their names as `OsString`s (via `file_name()`) and their paths as `PathBuf`s
+/// (via `path()`), so names which aren't valid UTF-8 are preserved; this adds a convenient way to
+/// display them.
+pub trait DirEntryExt {
+    /// Returns the file name of this entry as a string, replacing any invalid UTF-8 sequences with
+    /// `U+FFFD REPLACEMENT CHARACTER`. Use `file_name()` if the exact name is needed.
+    fn file_name_lossy(&self) -> String;
+}
+
+impl DirEntryExt for fs::DirEntry {
+    fn file_name_lossy(&self) -> String {
+        self.file_name().to_string_lossy().into_owned()
+    }
+}
+
+#[cfg(feature = "async")]
+impl DirEntryExt for ::tokio::fs::DirEntry {
+    fn file_name_lossy(&self) -> String {
+        self.file_name().to_string_lossy().into_owned()
+    }
+}
 
 /// Options and flags which can be used to configure how a file is opened.
 ///
@@ -112,6 +141,11 @@ impl OpenOptions {
             .create_new(self.create_new);
         open_options
     }
+
+    /// Returns whether opening a file with these options could modify it.
+    fn is_writable(&self) -> bool {
+        self.write || self.append || self.truncate || self.create || self.create_new
+    }
 }
 
 /// Provides access to file I/O.
@@ -263,4 +297,308 @@ pub trait Fs {
     /// See [std::path::Path.exists](https://doc.rust-lang.org/std/path/struct.Path.html#method.exists)
     /// for more information.
     fn exists<P: AsRef<Path>>(&self, path: P) -> bool;
+
+    /// Returns the total size in bytes of all regular files at or below `path`.
+    ///
+    /// Like `metadata()`, a symbolic link at `path` itself is followed. Within the tree, regular
+    /// files contribute their length and directories contribute zero. A symbolic link found while
+    /// walking the tree contributes the length of its target if that is a regular file, and zero
+    /// otherwise: links to directories are not descended into, so that cycles and shared subtrees
+    /// aren't counted more than once, and broken links are ignored.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use io_providers::{Fs, TempFs};
+    ///
+    /// let mut fs = TempFs::new().unwrap();
+    /// fs.create_dir("/a").unwrap();
+    /// fs.write("/a/one.txt", "1").unwrap();
+    /// fs.write("/two.txt", "22").unwrap();
+    /// assert_eq!(3, fs.recursive_size("/").unwrap());
+    /// ```
+    fn recursive_size<P: AsRef<Path>>(&self, path: P) -> io::Result<u64> {
+        let path = path.as_ref();
+        let metadata = self.metadata(path)?;
+        if !metadata.is_dir() {
+            return Ok(if metadata.is_file() {
+                metadata.len()
+            } else {
+                0
+            });
+        }
+        let mut total = 0;
+        for entry in self.read_dir(path)? {
+            let entry_path = path.join(entry?.file_name());
+            let entry_metadata = self.symlink_metadata(&entry_path)?;
+            total += if entry_metadata.file_type().is_symlink() {
+                match self.metadata(&entry_path) {
+                    Ok(ref target) if target.is_file() => target.len(),
+                    _ => 0,
+                }
+            } else {
+                self.recursive_size(&entry_path)?
+            };
+        }
+        Ok(total)
+    }
+
+    /// Removes a directory at this path and all its contents, continuing past failures to remove
+    /// as much as possible.
+    ///
+    /// Returns every path which couldn't be removed along with the reason, in the order in which
+    /// they were encountered. A directory whose contents couldn't all be removed is not itself
+    /// reported. Like `remove_dir_all()`, symbolic links are removed rather than followed, and
+    /// if the path is a file nothing is removed and an error of kind
+    /// `io::ErrorKind::NotADirectory` is reported for it.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use std::io;
+    /// use std::path::PathBuf;
+    /// use io_providers::{Fs, TempFs};
+    ///
+    /// let mut fs = TempFs::new().unwrap();
+    /// fs.create_dir("/cache").unwrap();
+    /// fs.write("/cache/a", "").unwrap();
+    /// fs.write("/cache/b", "").unwrap();
+    /// fs.inject_error("/cache/a", io::ErrorKind::PermissionDenied).unwrap();
+    ///
+    /// let failures = fs.remove_dir_all_best_effort("/cache").unwrap_err();
+    /// assert_eq!(1, failures.len());
+    /// assert_eq!(PathBuf::from("/cache/a"), failures[0].0);
+    /// assert!(!fs.exists("/cache/b"));
+    /// ```
+    fn remove_dir_all_best_effort<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> Result<(), Vec<(PathBuf, io::Error)>>
+    where
+        Self: Sized,
+    {
+        let mut failures = Vec::new();
+        remove_dir_tree(
+            self,
+            path.as_ref(),
+            |fs, path| fs.symlink_metadata(path),
+            false,
+            &mut failures,
+        );
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures)
+        }
+    }
+
+    /// Returns a read-only view of this provider, which can be handed to code that should not be
+    /// able to modify the filesystem.
+    ///
+    /// See [`FsRead`](trait.FsRead.html) for more information.
+    fn readonly(&self) -> &dyn FsRead
+    where
+        Self: Sized,
+    {
+        self
+    }
+
+    /// Opens the file at `rel`, resolved relative to the directory `base` rather than the current
+    /// working directory, like `openat()`.
+    ///
+    /// If `rel` is absolute, `base` is ignored. The joined path is passed to
+    /// [`open()`](#tymethod.open) unchanged, so it is subject to the same handling as any other
+    /// path; in particular, a `TempFs` keeps `..` components from leaving its root.
+    fn open_at<P: AsRef<Path>, Q: AsRef<Path>>(
+        &mut self,
+        base: P,
+        rel: Q,
+        open_options: &OpenOptions,
+    ) -> io::Result<fs::File> {
+        self.open(resolve_at(base.as_ref(), rel.as_ref()), open_options)
+    }
+
+    /// Returns the metadata of the file or directory at `rel`, resolved relative to the directory
+    /// `base` rather than the current working directory, like `fstatat()`.
+    ///
+    /// If `rel` is absolute, `base` is ignored. Otherwise `rel` is joined onto `base` without
+    /// normalizing it, so a `..` following a symbolic link names the parent of the link's target.
+    /// A `TempFs` resolves the components of `rel` one at a time in the same way, stopping `..` at
+    /// its root, after checking the joined path against its
+    /// [`NormalizationPolicy`](struct.NormalizationPolicy.html).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use io_providers::{Fs, TempFs};
+    ///
+    /// let fs = TempFs::from_layout(&[("/a/config", Some("a")), ("/b/config", Some("bb"))]).unwrap();
+    /// assert_eq!(1, fs.metadata_at("/a", "config").unwrap().len());
+    /// assert_eq!(2, fs.metadata_at("/b", "config").unwrap().len());
+    /// assert_eq!(2, fs.metadata_at("/a", "../b/config").unwrap().len());
+    /// ```
+    fn metadata_at<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        base: P,
+        rel: Q,
+    ) -> io::Result<fs::Metadata> {
+        self.metadata(resolve_at(base.as_ref(), rel.as_ref()))
+    }
+
+    /// Returns an iterator over the entries within the directory at `rel`, resolved relative to
+    /// the directory `base` rather than the current working directory.
+    ///
+    /// See [`metadata_at()`](#method.metadata_at) for how the path is resolved.
+    fn read_dir_at<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        base: P,
+        rel: Q,
+    ) -> io::Result<fs::ReadDir> {
+        self.read_dir(resolve_at(base.as_ref(), rel.as_ref()))
+    }
+}
+
+/// Resolves `rel` relative to `base` for the `*_at()` methods of `Fs`.
+fn resolve_at(base: &Path, rel: &Path) -> PathBuf {
+    base.join(rel)
+}
+
+/// Removes the directory (or symbolic link) `path` and its contents like `remove_tree()`, but
+/// adds an error of kind `io::ErrorKind::NotADirectory` to `failures` without removing anything
+/// if `path` is a file, matching `std::fs::remove_dir_all()`.
+///
+/// Entries are inspected using `metadata`, which doesn't follow symbolic links, so that providers
+/// can do so without it counting as a call to `symlink_metadata()`.
+pub(crate) fn remove_dir_tree<F: Fs>(
+    fs: &mut F,
+    path: &Path,
+    metadata: fn(&F, &Path) -> io::Result<fs::Metadata>,
+    stop_on_failure: bool,
+    failures: &mut Vec<(PathBuf, io::Error)>,
+) {
+    match metadata(fs, path) {
+        Ok(ref metadata) if metadata.is_file() => failures.push((
+            path.to_path_buf(),
+            io::Error::new(io::ErrorKind::NotADirectory, "not a directory"),
+        )),
+        Ok(_) => remove_tree(fs, path, metadata, stop_on_failure, failures),
+        Err(e) => failures.push((path.to_path_buf(), e)),
+    }
+}
+
+/// Removes `path` and (if it is a directory) its contents one entry at a time using `fs`, adding
+/// each path which couldn't be removed to `failures`. Stops at the first failure if
+/// `stop_on_failure` is set.
+fn remove_tree<F: Fs>(
+    fs: &mut F,
+    path: &Path,
+    metadata: fn(&F, &Path) -> io::Result<fs::Metadata>,
+    stop_on_failure: bool,
+    failures: &mut Vec<(PathBuf, io::Error)>,
+) {
+    let entry_metadata = match metadata(fs, path) {
+        Ok(entry_metadata) => entry_metadata,
+        Err(e) => return failures.push((path.to_path_buf(), e)),
+    };
+    if !entry_metadata.is_dir() {
+        if let Err(e) = fs.remove_file(path) {
+            failures.push((path.to_path_buf(), e));
+        }
+        return;
+    }
+
+    let names: io::Result<Vec<OsString>> = fs
+        .read_dir(path)
+        .and_then(|entries| entries.map(|e| e.map(|e| e.file_name())).collect());
+    let names = match names {
+        Ok(names) => names,
+        Err(e) => return failures.push((path.to_path_buf(), e)),
+    };
+    let previous_failures = failures.len();
+    for name in names {
+        remove_tree(fs, &path.join(name), metadata, stop_on_failure, failures);
+        if stop_on_failure && failures.len() > previous_failures {
+            return;
+        }
+    }
+    if failures.len() == previous_failures {
+        if let Err(e) = fs.remove_dir(path) {
+            failures.push((path.to_path_buf(), e));
+        }
+    }
+}
+
+/// Provides read-only access to file I/O.
+///
+/// Unlike [`Fs`](trait.Fs.html), this trait is object-safe, so a `&dyn FsRead` can be handed to
+/// components which only need to read; they cannot call mutating methods such as `write()` or
+/// `remove_file()`. It is implemented for every `Fs`, and a reference can be obtained using
+/// [`Fs::readonly()`](trait.Fs.html#method.readonly).
+///
+/// Since the method names are shared with `Fs`, calls must be fully qualified if both traits are
+/// in scope and the concrete provider type is known; calls through a `&dyn FsRead` are never
+/// ambiguous.
+///
+/// # Examples
+///
+/// ```
+/// extern crate io_providers;
+///
+/// use std::path::Path;
+/// use io_providers::fs::{Fs, FsRead, TempFs};
+///
+/// /// Reports the size of a file, without being able to modify anything.
+/// fn report(fs: &dyn FsRead, path: &Path) -> String {
+///     match fs.metadata(path) {
+///         Ok(metadata) => format!("{}: {} bytes", path.display(), metadata.len()),
+///         Err(_) => format!("{}: missing", path.display()),
+///     }
+/// }
+///
+/// fn main() {
+///     let mut fs = TempFs::new().unwrap();
+///     Fs::write(&mut fs, "/data.txt", "12345").unwrap();
+///
+///     assert_eq!("/data.txt: 5 bytes", report(fs.readonly(), Path::new("/data.txt")));
+///     assert_eq!("/nope.txt: missing", report(fs.readonly(), Path::new("/nope.txt")));
+/// }
+/// ```
+pub trait FsRead {
+    /// Reads the entire contents of a file into a bytes vector.
+    ///
+    /// See [`Fs::read()`](trait.Fs.html#tymethod.read).
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    /// Queries the file system to get information about a file, directory, etc.
+    ///
+    /// See [`Fs::metadata()`](trait.Fs.html#tymethod.metadata).
+    fn metadata(&self, path: &Path) -> io::Result<fs::Metadata>;
+
+    /// Returns an iterator over the entries within a directory.
+    ///
+    /// See [`Fs::read_dir()`](trait.Fs.html#tymethod.read_dir).
+    fn read_dir(&self, path: &Path) -> io::Result<fs::ReadDir>;
+
+    /// Returns whether the path points at an existing entity.
+    ///
+    /// See [`Fs::exists()`](trait.Fs.html#tymethod.exists).
+    fn exists(&self, path: &Path) -> bool;
+}
+
+impl<F: Fs> FsRead for F {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        Fs::read(self, path)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<fs::Metadata> {
+        Fs::metadata(self, path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<fs::ReadDir> {
+        Fs::read_dir(self, path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        Fs::exists(self, path)
+    }
 }
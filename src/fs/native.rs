@@ -14,58 +14,72 @@ impl Fs for NativeFs {
         path: P,
         open_options: &OpenOptions,
     ) -> io::Result<fs::File> {
+        ::check_real_io_allowed("NativeFs");
         open_options.as_std().open(path)
     }
 
     fn copy<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, from: P, to: Q) -> io::Result<u64> {
+        ::check_real_io_allowed("NativeFs");
         fs::copy(from, to)
     }
 
     fn create_dir<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        ::check_real_io_allowed("NativeFs");
         fs::create_dir(path)
     }
 
     fn create_dir_all<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        ::check_real_io_allowed("NativeFs");
         fs::create_dir_all(path)
     }
 
     fn hard_link<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, src: P, dst: Q) -> io::Result<()> {
+        ::check_real_io_allowed("NativeFs");
         fs::hard_link(src, dst)
     }
 
     fn metadata<P: AsRef<Path>>(&self, path: P) -> io::Result<fs::Metadata> {
+        ::check_real_io_allowed("NativeFs");
         fs::metadata(path)
     }
 
     fn read<P: AsRef<Path>>(&self, path: P) -> io::Result<Vec<u8>> {
+        ::check_real_io_allowed("NativeFs");
         fs::read(path)
     }
 
     fn read_dir<P: AsRef<Path>>(&self, path: P) -> io::Result<fs::ReadDir> {
+        ::check_real_io_allowed("NativeFs");
         fs::read_dir(path)
     }
 
     fn read_link<P: AsRef<Path>>(&self, path: P) -> io::Result<PathBuf> {
+        ::check_real_io_allowed("NativeFs");
         fs::read_link(path)
     }
 
     fn read_to_string<P: AsRef<Path>>(&self, path: P) -> io::Result<String> {
+        ::check_real_io_allowed("NativeFs");
         fs::read_to_string(path)
     }
 
     fn remove_dir<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        ::check_real_io_allowed("NativeFs");
         fs::remove_dir(path)
     }
 
     fn remove_dir_all<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        ::check_real_io_allowed("NativeFs");
         fs::remove_dir_all(path)
     }
 
     fn remove_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        ::check_real_io_allowed("NativeFs");
         fs::remove_file(path)
     }
 
     fn rename<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, from: P, to: Q) -> io::Result<()> {
+        ::check_real_io_allowed("NativeFs");
         fs::rename(from, to)
     }
 
@@ -74,18 +88,22 @@ impl Fs for NativeFs {
         path: P,
         perm: fs::Permissions,
     ) -> io::Result<()> {
+        ::check_real_io_allowed("NativeFs");
         fs::set_permissions(path, perm)
     }
 
     fn symlink_metadata<P: AsRef<Path>>(&self, path: P) -> io::Result<fs::Metadata> {
+        ::check_real_io_allowed("NativeFs");
         fs::symlink_metadata(path)
     }
 
     fn write<P: AsRef<Path>, C: AsRef<[u8]>>(&mut self, path: P, contents: C) -> io::Result<()> {
+        ::check_real_io_allowed("NativeFs");
         fs::write(path, contents)
     }
 
     fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        ::check_real_io_allowed("NativeFs");
         path.as_ref().exists()
     }
 }
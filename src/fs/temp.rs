@@ -1,44 +1,1009 @@
+use std::fmt;
 use std::fs;
+#[cfg(feature = "async")]
+use std::future::Future;
 use std::io;
-use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+use std::path::{self, Component, Path, PathBuf, Prefix};
+#[cfg(feature = "async")]
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
+use std::thread;
+use std::time::SystemTime;
 
 use tempfile::{tempdir, TempDir};
+#[cfg(feature = "async")]
+use tokio;
 
-use fs::{Fs, OpenOptions};
+#[cfg(feature = "async")]
+use fs::async_fs;
+use fs::{self as fs_mod, Fs, OpenOptions};
+#[cfg(feature = "async")]
+use fs::{AsyncFs, FsFuture};
+use LineEnding;
 
 /// Provides access to file I/O in a chroot-like temporary filesystem, located in the system's
 /// default temp directory. This temporary directory acts like the root of the filesystem: all
 /// absolute paths are relative to it, and any path which would traverse out of it is considered
 /// invalid.
 ///
+/// Paths are anchored to the root of the temporary filesystem as follows:
+///   * Relative paths (`foo`) and absolute paths (`/foo`, or `\foo` on Windows) are both resolved
+///     from the root.
+///   * On Windows, paths with a drive prefix (`C:\foo`, the drive-relative `C:foo`, and the
+///     verbatim `\\?\C:\foo`) are resolved from the root if the drive matches the default drive
+///     (see [`set_default_drive()`](#method.set_default_drive)); drive letters are compared
+///     case-insensitively. Any other prefix (another drive, or a UNC or device path) is invalid.
+///
 /// Details to be aware of:
 ///   * This is NOT intended to act as a secure sandbox; while it ought to handle edge cases such as
 ///     path traversals and symbolic links correctly, no attempt has been made to verify that there
 ///     is no way to circumvent this.
-///   * [`Fs::create_dir_all()`](fs/trait.Fs.html#tymethod.create_dir_all) is not currently
-///     implemented. It is possible to implement, but it's non-trivial to handle path traversals and
-///     symlinks for this function.
+///   * [`Fs::create_dir_all()`](fs/trait.Fs.html#tymethod.create_dir_all) creates one directory
+///     at a time, resolving each ancestor like any other path, so that path traversals and symbolic
+///     links can't escape the root.
+///   * Line endings in data written using [`Fs::write()`](fs/trait.Fs.html#tymethod.write) can be
+///     translated using [`set_line_ending()`](#method.set_line_ending). Files written through a
+///     handle returned by [`Fs::open()`](fs/trait.Fs.html#tymethod.open) are never translated.
+///   * The number of files and directories which can be created may be capped using
+///     [`set_max_nodes()`](#method.set_max_nodes), e.g. to stop a runaway property test from
+///     filling the disk.
+///   * Reading a file using [`Fs::read()`](fs/trait.Fs.html#tymethod.read) or
+///     [`Fs::read_to_string()`](fs/trait.Fs.html#tymethod.read_to_string) does not change its
+///     accessed time (like a `noatime` mount), unless enabled using
+///     [`set_update_atime()`](#method.set_update_atime).
+///   * Errors can be injected for specific paths using [`inject_error()`](#method.inject_error),
+///     and checked to have been triggered using [`verify()`](#method.verify).
+///   * The contents returned by reads can be transformed using
+///     [`set_read_transform()`](#method.set_read_transform).
+///   * Repeated separators, trailing separators and `.` components are handled according to a
+///     [`NormalizationPolicy`](struct.NormalizationPolicy.html), which may be changed using
+///     [`set_normalization_policy()`](#method.set_normalization_policy).
+///
+/// Failures are reported with the following error kinds, checked in this order:
+///   * `io::ErrorKind::InvalidInput` if the path is longer than the maximum path length (see
+///     [`set_max_path_len()`](#method.set_max_path_len)).
+///   * `io::ErrorKind::Other` if the path is invalid, e.g. because it traverses out of the root.
+///   * `io::ErrorKind::NotFound` if the parent directory of the path doesn't exist, e.g. for
+///     `Fs::write("/missing_dir/file", ..)`.
+///   * `io::ErrorKind::PermissionDenied` if the operation would modify the filesystem while it is
+///     read-only (see [`set_read_only()`](#method.set_read_only)).
+///   * `io::ErrorKind::Other` if the node limit has been reached.
+///   * Otherwise the error reported by the host, e.g. `io::ErrorKind::AlreadyExists` when opening
+///     an existing file with `create_new`, or `io::ErrorKind::NotFound` when reading a file which
+///     doesn't exist.
+///
+/// Symbolic links are handled by the host, so `Fs::metadata()` (which follows links) fails with
+/// `io::ErrorKind::NotFound` for a dangling link, and with the host's `ELOOP` error for a cycle of
+/// links. Since `io::ErrorKind::FilesystemLoop` is unstable, the `kind()` of the latter can't be
+/// matched by name: Rust versions which map `ELOOP` report `FilesystemLoop`, and older versions
+/// report `io::ErrorKind::Other`, so match on `raw_os_error()` instead. `Fs::symlink_metadata()`
+/// doesn't follow links, and so succeeds in both cases.
 #[derive(Debug)]
 pub struct TempFs {
     temp_dir: TempDir,
+    default_drive: u8,
+    line_ending: LineEnding,
+    max_nodes: Option<usize>,
+    max_path_len: Option<usize>,
+    update_atime: bool,
+    read_only: bool,
+    normalization_policy: NormalizationPolicy,
+    #[cfg(unix)]
+    umask: Option<u32>,
+    counters: Counters,
+    node_count: NodeCount,
+    // Errors to be returned by the next operation on each (already changed) path
+    injected_errors: Mutex<Vec<(PathBuf, io::ErrorKind)>>,
+    strict_injections: bool,
+    read_transforms: Vec<(PathBuf, ReadTransform)>,
+    global_read_transform: Option<ReadTransform>,
+}
+
+/// A transformation applied to the contents of files as they are read.
+pub type ReadTransformFn = Box<dyn Fn(&[u8]) -> Vec<u8> + Send + Sync>;
+
+struct ReadTransform(ReadTransformFn);
+
+impl fmt::Debug for ReadTransform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ReadTransform")
+    }
+}
+
+/// Controls how a [`TempFs`](struct.TempFs.html) treats redundant parts of the paths passed to it,
+/// before they are resolved.
+///
+/// The default policy collapses repeated separators, strips trailing separators and removes `.`
+/// components, which matches how most operating systems resolve paths (except that a trailing
+/// separator normally requires the path to be a directory). Since a `TempFs` stores files on the
+/// host filesystem, paths which differ only in these respects always name the same file; the
+/// stricter policies instead reject or reinterpret such paths, to catch code which builds paths
+/// carelessly.
+///
+/// ## Example
+///
+/// ```
+/// use io_providers::fs::{Fs, NormalizationPolicy, TempFs};
+///
+/// let mut fs = TempFs::new().unwrap();
+/// fs.write("/a.txt", "contents").unwrap();
+/// assert!(fs.read("//a.txt").is_ok());
+///
+/// fs.set_normalization_policy(NormalizationPolicy {
+///     collapse_separators: false,
+///     ..NormalizationPolicy::default()
+/// });
+/// assert!(fs.read("//a.txt").is_err());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NormalizationPolicy {
+    /// Whether repeated separators (`a//b`) are treated as a single separator. If `false`, a path
+    /// containing repeated separators is invalid, and fails with an error of kind
+    /// `io::ErrorKind::Other`.
+    pub collapse_separators: bool,
+
+    /// Whether trailing separators (`a/`) are ignored. If `false`, a path with a trailing
+    /// separator must be a directory (or a symbolic link to one), as with POSIX path resolution:
+    /// e.g. `Fs::metadata("file.txt/")` fails, and `Fs::write("dir/")` doesn't create a file.
+    /// The resulting errors are reported by the host.
+    pub strip_trailing_separator: bool,
+
+    /// Whether `.` components (`a/./b`) are removed. If `false`, a path containing a `.`
+    /// component is invalid, and fails with an error of kind `io::ErrorKind::Other`.
+    pub remove_cur_dir: bool,
+}
+
+impl Default for NormalizationPolicy {
+    fn default() -> Self {
+        NormalizationPolicy {
+            collapse_separators: true,
+            strip_trailing_separator: true,
+            remove_cur_dir: true,
+        }
+    }
+}
+
+impl NormalizationPolicy {
+    /// Checks `path` against this policy, returning whether a trailing separator must be kept.
+    fn check(&self, path: &Path) -> io::Result<bool> {
+        let path = path.to_string_lossy();
+        let is_separator = |c: char| c == '/' || (cfg!(windows) && c == '\\');
+        // A leading `\\` introduces a UNC or verbatim prefix on Windows
+        let skip = if cfg!(windows) && path.starts_with(is_separator) {
+            1
+        } else {
+            0
+        };
+        let segments: Vec<&str> = path[skip..].split(is_separator).collect();
+        // The first and last segments are empty for leading and trailing separators
+        let inner = if segments.len() > 2 {
+            &segments[1..segments.len() - 1]
+        } else {
+            &[][..]
+        };
+        if !self.collapse_separators && inner.iter().any(|s| s.is_empty()) {
+            return Err(io::Error::other("Invalid path: repeated separator"));
+        }
+        if !self.remove_cur_dir && segments.contains(&".") {
+            return Err(io::Error::other("Invalid path: `.` component"));
+        }
+        Ok(!self.strip_trailing_separator
+            && segments.len() > 1
+            && segments.last().is_some_and(|s| s.is_empty()))
+    }
+}
+
+/// Counts of the operations performed on a `TempFs`.
+#[derive(Debug, Default)]
+struct Counters {
+    read: AtomicUsize,
+    write: AtomicUsize,
+    metadata: AtomicUsize,
+    open: AtomicUsize,
+}
+
+/// The running count of the files and directories in a `TempFs`, kept while a node limit is set.
+/// `None` means that the count isn't known, and must be found by walking the filesystem. The
+/// count is shared with the futures of `AsyncFs` operations so that they can update it once they
+/// complete.
+#[derive(Debug, Default)]
+struct NodeCount(Arc<Mutex<Option<usize>>>);
+
+/// Records whether paths existed before an operation which may create or remove them, so that a
+/// `NodeCount` can be updated once the operation has completed.
+struct NodeChange {
+    count: Arc<Mutex<Option<usize>>>,
+    // The paths and whether each existed, or `None` if the count must be found again afterwards
+    paths: Option<Vec<(PathBuf, bool)>>,
+}
+
+impl NodeChange {
+    /// Updates the count for each path which was created or removed since the change began.
+    fn finish(self) {
+        let mut count = match self.count.lock() {
+            Ok(count) => count,
+            Err(_) => return,
+        };
+        let paths = match self.paths {
+            Some(ref paths) => paths,
+            None => return *count = None,
+        };
+        if let Some(ref mut count) = *count {
+            for (path, existed) in paths {
+                match (*existed, fs::symlink_metadata(path).is_ok()) {
+                    (false, true) => *count += 1,
+                    (true, false) => *count = count.saturating_sub(1),
+                    _ => {}
+                }
+            }
+        }
+    }
 }
 
 impl TempFs {
+    /// Creates a new `TempFs` containing the given files and directories.
+    ///
+    /// Each entry of `layout` is a path and either `Some(contents)` for a file or `None` for a
+    /// directory. Parent directories are created automatically, and entries are created in order,
+    /// so a later file with the same path replaces an earlier one.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use io_providers::{Fs, TempFs};
+    ///
+    /// let fs = TempFs::from_layout(&[
+    ///     ("/config/app.toml", Some("debug = true")),
+    ///     ("/data/cache", None),
+    /// ])
+    /// .unwrap();
+    /// assert_eq!("debug = true", fs.read_to_string("/config/app.toml").unwrap());
+    /// assert!(fs.metadata("/data/cache").unwrap().is_dir());
+    /// ```
+    pub fn from_layout<P: AsRef<Path>, C: AsRef<[u8]>>(
+        layout: &[(P, Option<C>)],
+    ) -> io::Result<TempFs> {
+        let mut temp_fs = TempFs::new()?;
+        for (path, contents) in layout {
+            let path = path.as_ref();
+            match contents {
+                Some(contents) => {
+                    if let Some(parent) = path.parent() {
+                        Fs::create_dir_all(&mut temp_fs, parent)?;
+                    }
+                    Fs::write(&mut temp_fs, path, contents)?;
+                }
+                None => Fs::create_dir_all(&mut temp_fs, path)?,
+            }
+        }
+        Ok(temp_fs)
+    }
+
     /// Creates a new `TempFs`.
     pub fn new() -> io::Result<TempFs> {
         Ok(TempFs {
             temp_dir: tempdir()?,
+            default_drive: b'C',
+            line_ending: LineEnding::Passthrough,
+            max_nodes: None,
+            max_path_len: None,
+            update_atime: false,
+            read_only: false,
+            normalization_policy: NormalizationPolicy::default(),
+            #[cfg(unix)]
+            umask: None,
+            counters: Counters::default(),
+            node_count: NodeCount::default(),
+            injected_errors: Mutex::new(Vec::new()),
+            strict_injections: false,
+            read_transforms: Vec::new(),
+            global_read_transform: None,
         })
     }
 
+    /// Sets whether reading a file updates its accessed time to the current time; `false` by
+    /// default.
+    ///
+    /// When enabled, the accessed time is updated by `Fs::read()`, `Fs::read_to_string()`, and by
+    /// `Fs::open()` with read access. Otherwise, `Fs::read()` and `Fs::read_to_string()` preserve
+    /// the accessed time regardless of how the host filesystem is mounted; reads through a handle
+    /// returned by `Fs::open()` are performed by the host and so follow its mount options.
+    ///
+    /// The same applies to the corresponding `AsyncFs` methods, for which the accessed time is
+    /// updated or restored once the returned future completes.
+    pub fn set_update_atime(&mut self, update_atime: bool) {
+        self.update_atime = update_atime;
+    }
+
+    /// Sets whether this temporary filesystem is read-only; `false` by default.
+    ///
+    /// While read-only, any operation which would modify the filesystem (including opening a file
+    /// with write, append, truncate or create access) fails with an error of kind
+    /// `io::ErrorKind::PermissionDenied`. This is useful for testing permission handling, since
+    /// real file permissions are not enforced for privileged users.
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// Sets a umask which is applied to the permissions of new files and directories, or `None` to
+    /// leave them as created by the host (the default).
+    ///
+    /// When set, a file created using `Fs::write()` or `Fs::open()` has the mode `0o666 & !umask`,
+    /// and a directory created using `Fs::create_dir()` or `Fs::create_dir_all()` has the mode
+    /// `0o777 & !umask`, regardless of the umask of the process. Existing files keep their
+    /// permissions, and `Fs::copy()` copies the permissions of the source file, as usual. The umask
+    /// is not applied by the `AsyncFs` implementation, except by `AsyncFs::create_dir_all()`,
+    /// which performs `Fs::create_dir_all()`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use std::os::unix::fs::PermissionsExt;
+    /// use io_providers::{Fs, TempFs};
+    ///
+    /// let mut fs = TempFs::new().unwrap();
+    /// fs.set_umask(Some(0o077));
+    /// fs.write("/secret.txt", "hunter2").unwrap();
+    /// assert_eq!(0o600, fs.metadata("/secret.txt").unwrap().permissions().mode() & 0o777);
+    /// ```
+    #[cfg(unix)]
+    pub fn set_umask(&mut self, umask: Option<u32>) {
+        self.umask = umask;
+    }
+
+    /// Returns the number of calls to `read()` and `read_to_string()` (through either `Fs` or
+    /// `AsyncFs`) since this filesystem was created or [`reset_counters()`](#method.reset_counters)
+    /// was called.
+    ///
+    /// Together with [`write_count()`](#method.write_count),
+    /// [`metadata_count()`](#method.metadata_count) and [`open_count()`](#method.open_count), this
+    /// makes it possible to assert that code doesn't perform more I/O than expected.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use io_providers::{Fs, TempFs};
+    ///
+    /// let mut fs = TempFs::new().unwrap();
+    /// fs.write("/config.txt", "verbose").unwrap();
+    /// fs.reset_counters();
+    ///
+    /// for _ in 0..3 {
+    ///     fs.read_to_string("/config.txt").unwrap();
+    /// }
+    /// assert_eq!(3, fs.read_count());
+    /// assert_eq!(0, fs.write_count());
+    /// ```
+    pub fn read_count(&self) -> usize {
+        self.counters.read.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of calls to `write()`.
+    ///
+    /// See [`read_count()`](#method.read_count) for more information.
+    pub fn write_count(&self) -> usize {
+        self.counters.write.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of calls to `metadata()` and `symlink_metadata()`.
+    ///
+    /// See [`read_count()`](#method.read_count) for more information.
+    pub fn metadata_count(&self) -> usize {
+        self.counters.metadata.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of calls to `open()`.
+    ///
+    /// See [`read_count()`](#method.read_count) for more information.
+    pub fn open_count(&self) -> usize {
+        self.counters.open.load(Ordering::Relaxed)
+    }
+
+    /// Resets the operation counters returned by [`read_count()`](#method.read_count) and friends
+    /// to zero.
+    pub fn reset_counters(&self) {
+        for counter in &[
+            &self.counters.read,
+            &self.counters.write,
+            &self.counters.metadata,
+            &self.counters.open,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Removes everything within this temporary filesystem and clears its per-operation state, so
+    /// that it can be reused as if it had just been created.
+    ///
+    /// This removes every file and directory (even if the filesystem is read-only), resets the
+    /// operation counters, and discards pending injected errors and per-path read transformations.
+    /// Configuration is preserved: the line ending, default drive, node and path length limits,
+    /// accessed time handling, read-only mode, normalization policy, umask, strict injections and
+    /// the global read transformation. The temporary directory itself is kept, so
+    /// [`path()`](#method.path) is unchanged.
+    ///
+    /// If strict injections are enabled (see
+    /// [`set_strict_injections()`](#method.set_strict_injections)), this first calls
+    /// [`verify()`](#method.verify), so it panics rather than discarding an injected error which
+    /// was never triggered.
+    pub fn reset(&mut self) -> io::Result<()> {
+        if self.strict_injections {
+            self.verify();
+        }
+        for entry in fs::read_dir(self.temp_dir.path())? {
+            let path = entry?.path();
+            if fs::symlink_metadata(&path)?.is_dir() {
+                fs::remove_dir_all(&path)?;
+            } else {
+                fs::remove_file(&path)?;
+            }
+        }
+        self.reset_counters();
+        *self.node_count.0.lock().unwrap() = None;
+        self.injected_errors.lock().unwrap().clear();
+        self.read_transforms.clear();
+        Ok(())
+    }
+
+    /// Returns the number of files (including symbolic links) in this temporary filesystem.
+    pub fn file_count(&self) -> io::Result<usize> {
+        let mut count = 0;
+        self.walk(&mut |metadata| {
+            if !metadata.is_dir() {
+                count += 1;
+            }
+        })?;
+        Ok(count)
+    }
+
+    /// Returns the number of directories in this temporary filesystem, not including the root.
+    pub fn dir_count(&self) -> io::Result<usize> {
+        let mut count = 0;
+        self.walk(&mut |metadata| {
+            if metadata.is_dir() {
+                count += 1;
+            }
+        })?;
+        Ok(count)
+    }
+
+    /// Returns the total length in bytes of all the regular files in this temporary filesystem.
+    pub fn total_bytes(&self) -> io::Result<u64> {
+        let mut total = 0;
+        self.walk(&mut |metadata| {
+            if metadata.is_file() {
+                total += metadata.len();
+            }
+        })?;
+        Ok(total)
+    }
+
+    /// Returns a fingerprint of the contents of the file at `path`, for cheaply checking whether
+    /// it has changed.
+    ///
+    /// This is a fast non-cryptographic hash (64-bit FNV-1a) of the file's bytes; it is stable
+    /// within a version of this crate, but the algorithm may change between versions, so hashes
+    /// should not be persisted. Reading the file for hashing is subject to the same accessed time
+    /// handling as [`Fs::read()`](fs/trait.Fs.html#tymethod.read).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use io_providers::{Fs, TempFs};
+    ///
+    /// let mut fs = TempFs::new().unwrap();
+    /// fs.write("/a.txt", "contents").unwrap();
+    /// fs.write("/b.txt", "contents").unwrap();
+    /// assert_eq!(fs.content_hash("/a.txt").unwrap(), fs.content_hash("/b.txt").unwrap());
+    /// ```
+    pub fn content_hash<P: AsRef<Path>>(&self, path: P) -> io::Result<u64> {
+        const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const FNV_PRIME: u64 = 0x0100_0000_01b3;
+        let contents = Fs::read(self, path)?;
+        Ok(contents.iter().fold(FNV_OFFSET_BASIS, |hash, &b| {
+            (hash ^ u64::from(b)).wrapping_mul(FNV_PRIME)
+        }))
+    }
+
+    /// Creates a file at `path` (replacing any existing file) which is `len` bytes long and reads
+    /// as zeros, without writing its contents.
+    ///
+    /// The file is created as a sparse file where the host filesystem supports them (as most do),
+    /// so `len` may be far larger than the available disk space or memory; only regions which are
+    /// later written to (e.g. after seeking a handle returned by
+    /// [`Fs::open()`](fs/trait.Fs.html#tymethod.open)) take up space. This is useful for testing
+    /// size-threshold logic using `Fs::metadata()`. Note that reading the whole file (e.g. using
+    /// `Fs::read()`) still allocates `len` bytes.
+    ///
+    /// This is subject to the same checks as opening the file for writing using `Fs::open()`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use io_providers::{Fs, TempFs};
+    ///
+    /// let mut fs = TempFs::new().unwrap();
+    /// fs.create_sized("/huge.iso", 8 << 30).unwrap();
+    /// assert_eq!(8 << 30, fs.metadata("/huge.iso").unwrap().len());
+    /// ```
+    pub fn create_sized<P: AsRef<Path>>(&mut self, path: P, len: u64) -> io::Result<()> {
+        let file = Fs::open(
+            self,
+            path,
+            OpenOptions::new().write(true).create(true).truncate(true),
+        )?;
+        file.set_len(len)
+    }
+
+    /// Injects an error of the given kind, which is returned by the next operation on `path`
+    /// instead of performing it.
+    ///
+    /// The error is returned once, by whichever operation next resolves `path` (including
+    /// operations on entries within a directory being removed by `Fs::remove_dir_all()`), and is
+    /// then discarded; `Fs::exists()` doesn't trigger it. This makes it possible to test error
+    /// handling for failures, such as permission errors, which can't easily be caused for real.
+    ///
+    /// Returns an error if `path` is invalid or its parent directory doesn't exist.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use std::io;
+    /// use io_providers::{Fs, TempFs};
+    ///
+    /// let mut fs = TempFs::new().unwrap();
+    /// fs.write("/file.txt", "contents").unwrap();
+    /// fs.inject_error("/file.txt", io::ErrorKind::PermissionDenied).unwrap();
+    ///
+    /// assert_eq!(io::ErrorKind::PermissionDenied, fs.read("/file.txt").unwrap_err().kind());
+    /// assert!(fs.read("/file.txt").is_ok());
+    /// ```
+    pub fn inject_error<P: AsRef<Path>>(&mut self, path: P, kind: io::ErrorKind) -> io::Result<()> {
+        let path = self.resolve_link_path(path)?;
+        self.injected_errors.lock().unwrap().push((path, kind));
+        Ok(())
+    }
+
+    /// Sets a transformation which is applied to the contents of the file at `path` whenever it is
+    /// read using `Fs::read()` or `Fs::read_to_string()`, e.g. to simulate on-the-fly
+    /// decompression or corruption. Any previous transformation for `path` is replaced.
+    ///
+    /// The transformation is associated with the file which `path` resolves to when this is
+    /// called, so it also applies when reading the file through a different path (such as a
+    /// symbolic link to it). It applies after any error injected using
+    /// [`inject_error()`](#method.inject_error): a read which returns an injected error doesn't
+    /// invoke the transformation. It is applied before the global transformation, if any (see
+    /// [`set_global_read_transform()`](#method.set_global_read_transform)), and for
+    /// `Fs::read_to_string()`, before the contents are checked to be valid UTF-8. Reads through a
+    /// handle returned by `Fs::open()`, and reads using `AsyncFs`, are not transformed.
+    ///
+    /// Returns an error if `path` is invalid or its parent directory doesn't exist.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use io_providers::{Fs, TempFs};
+    ///
+    /// let mut fs = TempFs::new().unwrap();
+    /// fs.write("/greeting.txt", "hello").unwrap();
+    /// fs.set_read_transform("/greeting.txt", Box::new(|bytes| bytes.iter().rev().cloned().collect()))
+    ///     .unwrap();
+    /// assert_eq!("olleh", fs.read_to_string("/greeting.txt").unwrap());
+    /// ```
+    pub fn set_read_transform<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        transform: ReadTransformFn,
+    ) -> io::Result<()> {
+        let path = self.resolve_path(path)?;
+        self.read_transforms.retain(|(p, _)| *p != path);
+        self.read_transforms.push((path, ReadTransform(transform)));
+        Ok(())
+    }
+
+    /// Sets a transformation which is applied to the contents of every file read using
+    /// `Fs::read()` or `Fs::read_to_string()`, or `None` to remove it (the default). This is
+    /// useful for fuzzing, e.g. by perturbing every read.
+    ///
+    /// The global transformation is applied after the transformation for the file's path, if
+    /// any; see [`set_read_transform()`](#method.set_read_transform) for more information.
+    pub fn set_global_read_transform(&mut self, transform: Option<ReadTransformFn>) {
+        self.global_read_transform = transform.map(ReadTransform);
+    }
+
+    /// Removes all the transformations set using [`set_read_transform()`](#method.set_read_transform)
+    /// and [`set_global_read_transform()`](#method.set_global_read_transform).
+    pub fn clear_read_transforms(&mut self) {
+        self.read_transforms.clear();
+        self.global_read_transform = None;
+    }
+
+    /// Sets whether errors injected using [`inject_error()`](#method.inject_error) must all be
+    /// triggered; `false` by default.
+    ///
+    /// When enabled, dropping this filesystem calls [`verify()`](#method.verify), so that an
+    /// injected error which was never returned (e.g. because the code path expected to fail was
+    /// never taken) fails the test. Nothing is checked if the thread is already panicking.
+    pub fn set_strict_injections(&mut self, strict: bool) {
+        self.strict_injections = strict;
+    }
+
+    /// Panics if any error injected using [`inject_error()`](#method.inject_error) hasn't been
+    /// triggered yet, listing the paths and error kinds of those injections.
+    ///
+    /// ## Example
+    ///
+    /// ```should_panic
+    /// use std::io;
+    /// use io_providers::{Fs, TempFs};
+    ///
+    /// let mut fs = TempFs::new().unwrap();
+    /// fs.inject_error("/config.toml", io::ErrorKind::PermissionDenied).unwrap();
+    ///
+    /// // Panics, since nothing has tried to access "/config.toml"
+    /// fs.verify();
+    /// ```
+    pub fn verify(&self) {
+        let injected_errors = self.injected_errors.lock().unwrap();
+        if injected_errors.is_empty() {
+            return;
+        }
+        let unfired: Vec<String> = injected_errors
+            .iter()
+            .map(|(path, kind)| {
+                let relative = path.strip_prefix(self.temp_dir.path()).unwrap_or(path);
+                format!("{} ({:?})", Path::new("/").join(relative).display(), kind)
+            })
+            .collect();
+        panic!(
+            "{} injected error(s) were never triggered: {}",
+            unfired.len(),
+            unfired.join(", ")
+        );
+    }
+
+    /// Sets the maximum number of files and directories (not including the root) which may exist
+    /// in this temporary filesystem, or `None` for no limit (the default).
+    ///
+    /// Once the limit is reached, any operation which would create a new file or directory fails
+    /// with an error of kind `io::ErrorKind::Other`.
+    ///
+    /// The filesystem is walked once to count its contents, and the count is then kept up to date
+    /// by the operations of this `TempFs`. Changes made directly to the host directory (see
+    /// [`path()`](#method.path)) aren't noticed until the limit is set again or the filesystem is
+    /// [`reset()`](#method.reset).
+    pub fn set_max_nodes(&mut self, max_nodes: Option<usize>) {
+        self.max_nodes = max_nodes;
+        *self.node_count.0.lock().unwrap() = None;
+    }
+
+    /// Sets the maximum length in bytes of the paths which may be passed to this temporary
+    /// filesystem, or `None` for no limit (the default).
+    ///
+    /// Any operation given a longer path fails with an error of kind
+    /// `io::ErrorKind::InvalidInput`, e.g. to test how code handles the 260-character limit of
+    /// older Windows APIs on any host. The limit applies to the path as given (so `/a/../b` is
+    /// longer than `/b`), not to the path within the host's temp directory.
+    pub fn set_max_path_len(&mut self, max_path_len: Option<usize>) {
+        self.max_path_len = max_path_len;
+    }
+
+    /// Sets the drive which is treated as the root of the temporary filesystem on Windows; `'C'`
+    /// by default. This has no effect on other platforms, where paths have no drive prefix.
+    ///
+    /// Panics if `drive` isn't an ASCII letter.
+    pub fn set_default_drive(&mut self, drive: char) {
+        assert!(
+            drive.is_ascii_alphabetic(),
+            "{:?} is not a valid drive letter",
+            drive
+        );
+        self.default_drive = drive.to_ascii_uppercase() as u8;
+    }
+
+    /// Sets how redundant parts of paths are treated; see
+    /// [`NormalizationPolicy`](struct.NormalizationPolicy.html) for more information.
+    pub fn set_normalization_policy(&mut self, normalization_policy: NormalizationPolicy) {
+        self.normalization_policy = normalization_policy;
+    }
+
+    /// Sets the line ending translation applied to data written using
+    /// [`Fs::write()`](fs/trait.Fs.html#tymethod.write).
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.line_ending = line_ending;
+    }
+
     /// Returns the path to the root of this temporary filesystem.
     pub fn path(&self) -> &Path {
         self.temp_dir.path()
     }
 
+    /// Performs `read` on the (already changed) `path`, updating or preserving its accessed time
+    /// as configured.
+    ///
+    /// When preserving, the accessed time is only restored if the host actually changed it (e.g.
+    /// on a `relatime` mount), so that the file's change time is otherwise left alone. Failing to
+    /// restore it doesn't fail the read.
+    fn read_with_atime<T, F: FnOnce(&Path) -> io::Result<T>>(
+        &self,
+        path: &Path,
+        read: F,
+    ) -> io::Result<T> {
+        let update = self.begin_read(path);
+        let result = read(path)?;
+        update.finish(path)?;
+        Ok(result)
+    }
+
+    /// Returns how the accessed time of the (already changed) `path` is to be handled once it has
+    /// been read.
+    fn begin_read(&self, path: &Path) -> AtimeUpdate {
+        if self.update_atime {
+            AtimeUpdate::Now
+        } else {
+            AtimeUpdate::Restore(accessed(path))
+        }
+    }
+
+    /// Returns the metadata of `path` without following a symbolic link in its final component,
+    /// like `Fs::symlink_metadata()`, for removing a tree without counting the calls.
+    fn tree_metadata(&self, path: &Path) -> io::Result<fs::Metadata> {
+        fs::symlink_metadata(self.change_link_path(path)?)
+    }
+
+    /// Returns whether reads of the (already changed) `path` are transformed.
+    fn has_read_transform(&self, path: &Path) -> bool {
+        self.global_read_transform.is_some() || self.read_transforms.iter().any(|(p, _)| p == path)
+    }
+
+    /// Applies the read transformations for the (already changed) `path` to `contents`.
+    fn transform_read(&self, path: &Path, mut contents: Vec<u8>) -> Vec<u8> {
+        if let Some((_, transform)) = self.read_transforms.iter().find(|(p, _)| p == path) {
+            contents = (transform.0)(&contents);
+        }
+        if let Some(ref transform) = self.global_read_transform {
+            contents = (transform.0)(&contents);
+        }
+        contents
+    }
+
+    fn walk(&self, f: &mut dyn FnMut(&fs::Metadata)) -> io::Result<()> {
+        fn walk_dir(dir: &Path, f: &mut dyn FnMut(&fs::Metadata)) -> io::Result<()> {
+            for entry in fs::read_dir(dir)? {
+                let entry = entry?;
+                let metadata = fs::symlink_metadata(entry.path())?;
+                f(&metadata);
+                if metadata.is_dir() {
+                    walk_dir(&entry.path(), f)?;
+                }
+            }
+            Ok(())
+        }
+
+        walk_dir(self.temp_dir.path(), f)
+    }
+
+    /// Returns the number of files and directories in this temporary filesystem, not including
+    /// the root, walking it only if the running count isn't known.
+    fn node_count(&self) -> io::Result<usize> {
+        let mut cached = self.node_count.0.lock().unwrap();
+        if let Some(count) = *cached {
+            return Ok(count);
+        }
+        let mut count = 0;
+        self.walk(&mut |_| count += 1)?;
+        *cached = Some(count);
+        Ok(count)
+    }
+
+    /// Begins a change to the (already changed) `paths`, which may be created or removed, if the
+    /// running node count is kept.
+    fn begin_node_change(&self, paths: &[&Path]) -> Option<NodeChange> {
+        self.max_nodes?;
+        let paths = paths
+            .iter()
+            .map(|p| (p.to_path_buf(), fs::symlink_metadata(p).is_ok()))
+            .collect();
+        Some(NodeChange {
+            count: self.node_count.0.clone(),
+            paths: Some(paths),
+        })
+    }
+
+    /// Performs `op`, which may create or remove the (already changed) `paths`, keeping the
+    /// running node count up to date.
+    fn track_nodes<T, F: FnOnce() -> io::Result<T>>(
+        &self,
+        paths: &[&Path],
+        op: F,
+    ) -> io::Result<T> {
+        let change = self.begin_node_change(paths);
+        let result = op();
+        if let Some(change) = change {
+            change.finish();
+        }
+        result
+    }
+
+    /// Fails if creating a node at the (already changed) `path` would exceed the node limit.
+    fn check_can_create(&self, path: &Path) -> io::Result<()> {
+        if let Some(max_nodes) = self.max_nodes {
+            if fs::symlink_metadata(path).is_err() && self.node_count()? >= max_nodes {
+                return Err(io::Error::other(format!(
+                    "The TempFs limit of {} files and directories has been reached",
+                    max_nodes
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Performs `create` on the (already changed) `path`, then applies the umask to the
+    /// `requested` mode if a new node was created.
+    #[cfg_attr(not(unix), allow(unused_variables))]
+    fn create_with_umask<T, F: FnOnce(&Path) -> io::Result<T>>(
+        &self,
+        path: &Path,
+        requested: u32,
+        create: F,
+    ) -> io::Result<T> {
+        #[cfg(unix)]
+        {
+            if let Some(umask) = self.umask {
+                if fs::symlink_metadata(path).is_err() {
+                    let result = create(path)?;
+                    fs::set_permissions(path, fs::Permissions::from_mode(requested & !umask))?;
+                    return Ok(result);
+                }
+            }
+        }
+        create(path)
+    }
+
+    /// Fails with an error of kind `io::ErrorKind::PermissionDenied` if this filesystem is
+    /// read-only.
+    fn check_writable(&self) -> io::Result<()> {
+        if self.read_only {
+            Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "The TempFs is read-only",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Like `change_path()`, but also fails if this filesystem is read-only.
+    fn change_writable_path<P: AsRef<Path>>(&self, path: P) -> io::Result<PathBuf> {
+        let path = self.change_path(path)?;
+        self.check_writable()?;
+        Ok(path)
+    }
+
+    /// Like `change_path()`, but doesn't follow a symbolic link in the final component of the path,
+    /// so that the link itself can be inspected.
+    fn change_link_path<P: AsRef<Path>>(&self, path: P) -> io::Result<PathBuf> {
+        let path = self.resolve_link_path(path)?;
+        self.take_injected_error(&path)?;
+        Ok(path)
+    }
+
+    /// Resolves `path` to a path within the temporary directory for an operation, returning an
+    /// injected error instead if there is one.
     fn change_path<P: AsRef<Path>>(&self, path: P) -> io::Result<PathBuf> {
-        let exists = path.as_ref().exists();
-        let mut result: PathBuf = self.temp_dir.path().join(path);
+        let path = self.resolve_path(path)?;
+        self.take_injected_error(&path)?;
+        Ok(path)
+    }
+
+    /// Returns (and discards) the error injected for the (already changed) `path`, if any.
+    fn take_injected_error(&self, path: &Path) -> io::Result<()> {
+        let mut injected_errors = self.injected_errors.lock().unwrap();
+        match injected_errors.iter().position(|(p, _)| p == path) {
+            Some(index) => {
+                let (_, kind) = injected_errors.remove(index);
+                Err(io::Error::new(kind, "injected error"))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Checks `path` against the maximum path length and the normalization policy, returning
+    /// whether a trailing separator must be kept.
+    fn check_path(&self, path: &Path) -> io::Result<bool> {
+        if let Some(max_path_len) = self.max_path_len {
+            if path.as_os_str().len() > max_path_len {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Path is longer than {} bytes", max_path_len),
+                ));
+            }
+        }
+        self.normalization_policy.check(path)
+    }
+
+    fn resolve_link_path<P: AsRef<Path>>(&self, path: P) -> io::Result<PathBuf> {
+        let path = path.as_ref();
+        if self.check_path(path)? {
+            // A trailing separator follows the link, as with POSIX path resolution
+            return self.resolve_path(path);
+        }
+        match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => Ok(self.resolve_normalized_path(parent)?.join(name)),
+            _ => self.resolve_normalized_path(path),
+        }
+    }
+
+    /// Resolves `rel` relative to the directory `base` for the `*_at()` methods, returning the
+    /// equivalent path from the root.
+    ///
+    /// Symbolic links are followed as each component of `rel` is reached, so `..` names the
+    /// parent of the directory which was actually reached (as with `openat()`), and `..` at the
+    /// root stays at the root. Paths with a root or prefix are returned unchanged.
+    fn resolve_at(&self, base: &Path, rel: &Path) -> io::Result<PathBuf> {
+        let joined = base.join(rel);
+        if rel.has_root() || matches!(rel.components().next(), Some(Component::Prefix(_))) {
+            return Ok(joined);
+        }
+        let keep_trailing_separator = self.check_path(&joined)?;
+        let root = self.temp_dir.path();
+        let invalid = || io::Error::other("Invalid path");
+        let mut result = self.resolve_normalized_path(base)?;
+        for component in rel.components() {
+            match component {
+                Component::ParentDir if result != root => {
+                    let _ = result.pop();
+                }
+                Component::Normal(name) => {
+                    result.push(name);
+                    if result.exists() {
+                        result = result.canonicalize()?;
+                        if !result.starts_with(root) {
+                            return Err(invalid());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        let mut path = Path::new(path::MAIN_SEPARATOR_STR)
+            .join(result.strip_prefix(root).map_err(|_| invalid())?);
+        if keep_trailing_separator {
+            path.as_mut_os_string().push(path::MAIN_SEPARATOR_STR);
+        }
+        Ok(path)
+    }
+
+    fn resolve_path<P: AsRef<Path>>(&self, path: P) -> io::Result<PathBuf> {
+        let path = path.as_ref();
+        let keep_trailing_separator = self.check_path(path)?;
+        let mut result = self.resolve_normalized_path(path)?;
+        if keep_trailing_separator {
+            result.as_mut_os_string().push(path::MAIN_SEPARATOR_STR);
+        }
+        Ok(result)
+    }
+
+    /// Resolves `path` without checking it against the normalization policy.
+    fn resolve_normalized_path(&self, path: &Path) -> io::Result<PathBuf> {
+        let mut result: PathBuf = self.temp_dir.path().to_path_buf();
+        for component in path.components() {
+            match component {
+                Component::Prefix(prefix) => match prefix.kind() {
+                    Prefix::Disk(drive) | Prefix::VerbatimDisk(drive)
+                        if drive.to_ascii_uppercase() == self.default_drive => {}
+                    _ => return Err(io::Error::other("Invalid path")),
+                },
+                Component::RootDir => {}
+                component => result.push(component.as_os_str()),
+            }
+        }
+        let exists = result.exists();
 
         result = if exists {
             result.canonicalize()?
@@ -50,52 +1015,170 @@ impl TempFs {
                 .join(
                     result
                         .file_name()
-                        .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "Invalid path"))?,
+                        .ok_or_else(|| io::Error::other("Invalid path"))?,
                 )
         };
 
-        if result.starts_with(&self.temp_dir.path()) {
+        if result.starts_with(self.temp_dir.path()) {
             Ok(result)
         } else {
-            Err(io::Error::new(io::ErrorKind::Other, "Invalid path"))
+            Err(io::Error::other("Invalid path"))
         }
     }
 }
 
+impl Drop for TempFs {
+    fn drop(&mut self) {
+        if self.strict_injections && !thread::panicking() {
+            self.verify();
+        }
+    }
+}
+
+/// The error returned by `Fs::remove_dir_all()` for an entry which couldn't be removed, naming it.
+#[derive(Debug)]
+struct RemoveError {
+    path: PathBuf,
+    source: io::Error,
+}
+
+impl fmt::Display for RemoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "failed to remove {}: {}",
+            self.path.display(),
+            self.source
+        )
+    }
+}
+
+impl std::error::Error for RemoveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// How the accessed time of a file is handled once it has been read.
+enum AtimeUpdate {
+    /// The accessed time is set to the current time.
+    Now,
+    /// The accessed time is restored to the given time, if known, if the host changed it.
+    Restore(Option<SystemTime>),
+}
+
+impl AtimeUpdate {
+    /// Updates or restores the accessed time of the file at `path`, which has just been read.
+    fn finish(self, path: &Path) -> io::Result<()> {
+        match self {
+            AtimeUpdate::Now => set_accessed(path, SystemTime::now()),
+            AtimeUpdate::Restore(Some(previously_accessed)) => {
+                if accessed(path) != Some(previously_accessed) {
+                    let _ = set_accessed(path, previously_accessed);
+                }
+                Ok(())
+            }
+            AtimeUpdate::Restore(None) => Ok(()),
+        }
+    }
+}
+
+/// Returns the accessed time of the file at `path`, if it can be found.
+fn accessed(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.accessed()).ok()
+}
+
+/// Sets the accessed time of the file at `path` without reading from it.
+fn set_accessed(path: &Path, accessed: SystemTime) -> io::Result<()> {
+    let mut open_options = fs::OpenOptions::new();
+    open_options.read(true);
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+        const FILE_WRITE_ATTRIBUTES: u32 = 0x100;
+        open_options.access_mode(FILE_WRITE_ATTRIBUTES);
+    }
+    open_options
+        .open(path)?
+        .set_times(fs::FileTimes::new().set_accessed(accessed))
+}
+
 impl Fs for TempFs {
     fn open<P: AsRef<Path>>(
         &mut self,
         path: P,
         open_options: &OpenOptions,
     ) -> io::Result<fs::File> {
-        open_options.as_std().open(self.change_path(path)?)
+        self.counters.open.fetch_add(1, Ordering::Relaxed);
+        let path = self.change_path(path)?;
+        if open_options.is_writable() {
+            self.check_writable()?;
+        }
+        let file = if open_options.create || open_options.create_new {
+            self.check_can_create(&path)?;
+            self.track_nodes(&[&path], || {
+                self.create_with_umask(&path, 0o666, |p| open_options.as_std().open(p))
+            })?
+        } else {
+            open_options.as_std().open(&path)?
+        };
+        if self.update_atime && open_options.read {
+            set_accessed(&path, SystemTime::now())?;
+        }
+        Ok(file)
     }
 
     fn copy<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, from: P, to: Q) -> io::Result<u64> {
-        fs::copy(self.change_path(from)?, self.change_path(to)?)
+        let to = self.change_writable_path(to)?;
+        self.check_can_create(&to)?;
+        let from = self.change_path(from)?;
+        self.track_nodes(&[&to], || fs::copy(from, &to))
     }
 
     fn create_dir<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
-        fs::create_dir(self.change_path(path)?)
+        let path = self.change_writable_path(path)?;
+        self.check_can_create(&path)?;
+        self.track_nodes(&[&path], || {
+            self.create_with_umask(&path, 0o777, |p| fs::create_dir(p))
+        })
     }
 
-    #[allow(unused_variables)]
     fn create_dir_all<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
-        unimplemented!(
-            "It's difficult to implement path canonicalization correctly for create_dir_all()"
-        );
+        self.check_path(path.as_ref())?;
+        let mut ancestor = PathBuf::new();
+        for component in path.as_ref().components() {
+            ancestor.push(component.as_os_str());
+            if let Component::Normal(_) = component {
+                let path = self.change_path(&ancestor)?;
+                if !path.is_dir() {
+                    self.check_writable()?;
+                    self.check_can_create(&path)?;
+                    self.track_nodes(&[&path], || {
+                        self.create_with_umask(&path, 0o777, |p| fs::create_dir(p))
+                    })?;
+                }
+            }
+        }
+        Ok(())
     }
 
     fn hard_link<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, src: P, dst: Q) -> io::Result<()> {
-        fs::hard_link(self.change_path(src)?, self.change_path(dst)?)
+        let dst = self.change_writable_path(dst)?;
+        self.check_can_create(&dst)?;
+        let src = self.change_path(src)?;
+        self.track_nodes(&[&dst], || fs::hard_link(src, &dst))
     }
 
     fn metadata<P: AsRef<Path>>(&self, path: P) -> io::Result<fs::Metadata> {
+        self.counters.metadata.fetch_add(1, Ordering::Relaxed);
         fs::metadata(self.change_path(path)?)
     }
 
     fn read<P: AsRef<Path>>(&self, path: P) -> io::Result<Vec<u8>> {
-        fs::read(self.change_path(path)?)
+        self.counters.read.fetch_add(1, Ordering::Relaxed);
+        let path = self.change_path(path)?;
+        let contents = self.read_with_atime(&path, |p| fs::read(p))?;
+        Ok(self.transform_read(&path, contents))
     }
 
     fn read_dir<P: AsRef<Path>>(&self, path: P) -> io::Result<fs::ReadDir> {
@@ -103,27 +1186,76 @@ impl Fs for TempFs {
     }
 
     fn read_link<P: AsRef<Path>>(&self, path: P) -> io::Result<PathBuf> {
-        fs::read_link(self.change_path(path)?)
+        fs::read_link(self.change_link_path(path)?)
     }
 
     fn read_to_string<P: AsRef<Path>>(&self, path: P) -> io::Result<String> {
-        fs::read_to_string(self.change_path(path)?)
+        self.counters.read.fetch_add(1, Ordering::Relaxed);
+        let path = self.change_path(path)?;
+        if !self.has_read_transform(&path) {
+            return self.read_with_atime(&path, |p| fs::read_to_string(p));
+        }
+        let contents = self.read_with_atime(&path, |p| fs::read(p))?;
+        String::from_utf8(self.transform_read(&path, contents))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
     }
 
     fn remove_dir<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
-        fs::remove_dir(self.change_path(path)?)
+        let path = self.change_writable_path(path)?;
+        self.track_nodes(&[&path], || fs::remove_dir(&path))
     }
 
+    /// Unlike `std::fs::remove_dir_all()`, this removes the entries one at a time so that
+    /// [injected errors](struct.TempFs.html#method.inject_error) are honored. It stops at the
+    /// first failure, leaving any entries which were already removed deleted, and the error's
+    /// message names the path (within this filesystem) which couldn't be removed. See
+    /// [`Fs::remove_dir_all_best_effort()`](fs/trait.Fs.html#method.remove_dir_all_best_effort) to
+    /// remove as much as possible instead.
     fn remove_dir_all<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
-        fs::remove_dir_all(self.change_path(path)?)
+        let mut failures = Vec::new();
+        fs_mod::remove_dir_tree(
+            self,
+            path.as_ref(),
+            TempFs::tree_metadata,
+            true,
+            &mut failures,
+        );
+        match failures.pop() {
+            Some((path, source)) => {
+                Err(io::Error::new(source.kind(), RemoveError { path, source }))
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn remove_dir_all_best_effort<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> Result<(), Vec<(PathBuf, io::Error)>> {
+        let mut failures = Vec::new();
+        fs_mod::remove_dir_tree(
+            self,
+            path.as_ref(),
+            TempFs::tree_metadata,
+            false,
+            &mut failures,
+        );
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures)
+        }
     }
 
     fn remove_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
-        fs::remove_file(self.change_path(path)?)
+        let path = self.change_writable_path(path)?;
+        self.track_nodes(&[&path], || fs::remove_file(&path))
     }
 
     fn rename<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, from: P, to: Q) -> io::Result<()> {
-        fs::rename(self.change_path(from)?, self.change_path(to)?)
+        let from = self.change_writable_path(from)?;
+        let to = self.change_writable_path(to)?;
+        self.track_nodes(&[&from, &to], || fs::rename(&from, &to))
     }
 
     fn set_permissions<P: AsRef<Path>>(
@@ -131,18 +1263,382 @@ impl Fs for TempFs {
         path: P,
         perm: fs::Permissions,
     ) -> io::Result<()> {
-        fs::set_permissions(self.change_path(path)?, perm)
+        fs::set_permissions(self.change_writable_path(path)?, perm)
     }
 
     fn symlink_metadata<P: AsRef<Path>>(&self, path: P) -> io::Result<fs::Metadata> {
-        fs::symlink_metadata(self.change_path(path)?)
+        self.counters.metadata.fetch_add(1, Ordering::Relaxed);
+        fs::symlink_metadata(self.change_link_path(path)?)
     }
 
     fn write<P: AsRef<Path>, C: AsRef<[u8]>>(&mut self, path: P, contents: C) -> io::Result<()> {
-        fs::write(self.change_path(path)?, contents)
+        self.counters.write.fetch_add(1, Ordering::Relaxed);
+        let path = self.change_writable_path(path)?;
+        self.check_can_create(&path)?;
+        self.track_nodes(&[&path], || {
+            self.create_with_umask(&path, 0o666, |p| match self.line_ending {
+                LineEnding::Passthrough => fs::write(p, contents),
+                line_ending => {
+                    let mut translated = Vec::new();
+                    line_ending.append_translated(contents.as_ref(), &mut translated);
+                    fs::write(p, translated)
+                }
+            })
+        })
     }
 
     fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
-        self.change_path(path).map(|p| p.exists()).unwrap_or(false)
+        self.resolve_path(path).map(|p| p.exists()).unwrap_or(false)
+    }
+
+    fn open_at<P: AsRef<Path>, Q: AsRef<Path>>(
+        &mut self,
+        base: P,
+        rel: Q,
+        open_options: &OpenOptions,
+    ) -> io::Result<fs::File> {
+        let path = self.resolve_at(base.as_ref(), rel.as_ref())?;
+        Fs::open(self, path, open_options)
+    }
+
+    fn metadata_at<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        base: P,
+        rel: Q,
+    ) -> io::Result<fs::Metadata> {
+        Fs::metadata(self, self.resolve_at(base.as_ref(), rel.as_ref())?)
+    }
+
+    fn read_dir_at<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        base: P,
+        rel: Q,
+    ) -> io::Result<fs::ReadDir> {
+        Fs::read_dir(self, self.resolve_at(base.as_ref(), rel.as_ref())?)
+    }
+}
+
+/// A future which updates the running node count of a `TempFs` once the operation it wraps has
+/// completed.
+#[cfg(feature = "async")]
+struct NodeChangeFuture<T> {
+    future: FsFuture<T>,
+    change: Option<NodeChange>,
+}
+
+#[cfg(feature = "async")]
+impl<T> Future for NodeChangeFuture<T> {
+    type Output = io::Result<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let result = match self.future.as_mut().poll(cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => return Poll::Pending,
+        };
+        if let Some(change) = self.change.take() {
+            change.finish();
+        }
+        Poll::Ready(result)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T> Drop for NodeChangeFuture<T> {
+    fn drop(&mut self) {
+        // The operation may still complete in the background, so the count can't be trusted
+        if let Some(mut change) = self.change.take() {
+            change.paths = None;
+            change.finish();
+        }
+    }
+}
+
+/// A future which reads a file, then updates or restores its accessed time.
+#[cfg(feature = "async")]
+struct ReadFuture<T> {
+    future: FsFuture<T>,
+    path: PathBuf,
+    update: Option<AtimeUpdate>,
+}
+
+#[cfg(feature = "async")]
+impl<T> Future for ReadFuture<T> {
+    type Output = io::Result<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let result = match self.future.as_mut().poll(cx) {
+            Poll::Ready(result) => result?,
+            Poll::Pending => return Poll::Pending,
+        };
+        if let Some(update) = self.update.take() {
+            update.finish(&self.path)?;
+        }
+        Poll::Ready(Ok(result))
+    }
+}
+
+#[cfg(feature = "async")]
+impl TempFs {
+    /// Wraps the `future` of an operation which reads the (already changed) `path` so that its
+    /// accessed time is updated or preserved as configured once the read completes.
+    fn read_with_atime_async<T: Send + 'static>(
+        &self,
+        path: PathBuf,
+        future: FsFuture<T>,
+    ) -> FsFuture<T> {
+        let update = self.begin_read(&path);
+        Box::pin(ReadFuture {
+            future,
+            path,
+            update: Some(update),
+        })
+    }
+
+    /// Wraps the `future` of `open()` for the (already changed) `path` so that, if the accessed
+    /// time is updated on reads and the file is opened with read access, it is updated once the
+    /// file has been opened.
+    fn open_with_atime_async(
+        &self,
+        path: PathBuf,
+        open_options: &OpenOptions,
+        future: FsFuture<tokio::fs::File>,
+    ) -> FsFuture<tokio::fs::File> {
+        if !(self.update_atime && open_options.read) {
+            return future;
+        }
+        Box::pin(ReadFuture {
+            future,
+            path,
+            update: Some(AtimeUpdate::Now),
+        })
+    }
+
+    /// Wraps the `future` of an operation which may create or remove the (already changed)
+    /// `paths` so that it keeps the running node count up to date.
+    fn track_nodes_async<T: Send + 'static>(
+        &self,
+        paths: &[&Path],
+        future: FsFuture<T>,
+    ) -> FsFuture<T> {
+        match self.begin_node_change(paths) {
+            Some(change) => Box::pin(NodeChangeFuture {
+                future,
+                change: Some(change),
+            }),
+            None => future,
+        }
+    }
+}
+
+/// The `TempFs` operations complete on Tokio's blocking thread pool, exactly like
+/// [`TokioFs`](fs/struct.TokioFs.html), but are not affected by
+/// [`deny_real_io()`](fn.deny_real_io.html). Paths are resolved, and limits checked, before the returned
+/// future is created.
+#[cfg(feature = "async")]
+impl AsyncFs for TempFs {
+    fn open<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        open_options: &OpenOptions,
+    ) -> FsFuture<tokio::fs::File> {
+        self.counters.open.fetch_add(1, Ordering::Relaxed);
+        let path = match self.change_path(path) {
+            Ok(path) => path,
+            Err(e) => return async_fs::ready(Err(e)),
+        };
+        if open_options.is_writable() {
+            if let Err(e) = self.check_writable() {
+                return async_fs::ready(Err(e));
+            }
+        }
+        if open_options.create || open_options.create_new {
+            if let Err(e) = self.check_can_create(&path) {
+                return async_fs::ready(Err(e));
+            }
+        }
+        if open_options.create || open_options.create_new {
+            let future = Box::pin(async_fs::OpenFuture::new(
+                open_options.as_std(),
+                path.clone(),
+            ));
+            let future = self.track_nodes_async(&[&path], future);
+            return self.open_with_atime_async(path, open_options, future);
+        }
+        let future = Box::pin(async_fs::OpenFuture::new(
+            open_options.as_std(),
+            path.clone(),
+        ));
+        self.open_with_atime_async(path, open_options, future)
+    }
+
+    fn copy<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, from: P, to: Q) -> FsFuture<u64> {
+        let paths = self.change_path(from).and_then(|from| {
+            let to = self.change_writable_path(to)?;
+            self.check_can_create(&to)?;
+            Ok((from, to))
+        });
+        match paths {
+            Ok((from, to)) => {
+                self.track_nodes_async(&[&to], Box::pin(tokio::fs::copy(from, to.clone())))
+            }
+            Err(e) => async_fs::ready(Err(e)),
+        }
+    }
+
+    fn create_dir<P: AsRef<Path>>(&mut self, path: P) -> FsFuture<()> {
+        let path = self.change_writable_path(path).and_then(|path| {
+            self.check_can_create(&path)?;
+            Ok(path)
+        });
+        match path {
+            Ok(path) => {
+                self.track_nodes_async(&[&path], Box::pin(tokio::fs::create_dir(path.clone())))
+            }
+            Err(e) => async_fs::ready(Err(e)),
+        }
+    }
+
+    /// Since each directory has to be resolved after its parent has been created, this performs
+    /// the operation synchronously when called, and returns a future which is already complete.
+    fn create_dir_all<P: AsRef<Path>>(&mut self, path: P) -> FsFuture<()> {
+        async_fs::ready(Fs::create_dir_all(self, path))
+    }
+
+    fn hard_link<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, src: P, dst: Q) -> FsFuture<()> {
+        let paths = self.change_path(src).and_then(|src| {
+            let dst = self.change_writable_path(dst)?;
+            self.check_can_create(&dst)?;
+            Ok((src, dst))
+        });
+        match paths {
+            Ok((src, dst)) => {
+                self.track_nodes_async(&[&dst], Box::pin(tokio::fs::hard_link(src, dst.clone())))
+            }
+            Err(e) => async_fs::ready(Err(e)),
+        }
+    }
+
+    fn metadata<P: AsRef<Path>>(&self, path: P) -> FsFuture<fs::Metadata> {
+        self.counters.metadata.fetch_add(1, Ordering::Relaxed);
+        match self.change_path(path) {
+            Ok(path) => Box::pin(tokio::fs::metadata(path)),
+            Err(e) => async_fs::ready(Err(e)),
+        }
+    }
+
+    fn read<P: AsRef<Path>>(&self, path: P) -> FsFuture<Vec<u8>> {
+        self.counters.read.fetch_add(1, Ordering::Relaxed);
+        match self.change_path(path) {
+            Ok(path) => {
+                let future = Box::pin(tokio::fs::read(path.clone()));
+                self.read_with_atime_async(path, future)
+            }
+            Err(e) => async_fs::ready(Err(e)),
+        }
+    }
+
+    fn read_dir<P: AsRef<Path>>(&self, path: P) -> FsFuture<tokio::fs::ReadDir> {
+        match self.change_path(path) {
+            Ok(path) => Box::pin(tokio::fs::read_dir(path)),
+            Err(e) => async_fs::ready(Err(e)),
+        }
+    }
+
+    fn read_link<P: AsRef<Path>>(&self, path: P) -> FsFuture<PathBuf> {
+        match self.change_link_path(path) {
+            Ok(path) => Box::pin(tokio::fs::read_link(path)),
+            Err(e) => async_fs::ready(Err(e)),
+        }
+    }
+
+    fn read_to_string<P: AsRef<Path>>(&self, path: P) -> FsFuture<String> {
+        self.counters.read.fetch_add(1, Ordering::Relaxed);
+        match self.change_path(path) {
+            Ok(path) => {
+                let future = Box::pin(tokio::fs::read_to_string(path.clone()));
+                self.read_with_atime_async(path, future)
+            }
+            Err(e) => async_fs::ready(Err(e)),
+        }
+    }
+
+    fn remove_dir<P: AsRef<Path>>(&mut self, path: P) -> FsFuture<()> {
+        match self.change_writable_path(path) {
+            Ok(path) => {
+                self.track_nodes_async(&[&path], Box::pin(tokio::fs::remove_dir(path.clone())))
+            }
+            Err(e) => async_fs::ready(Err(e)),
+        }
+    }
+
+    /// Like `Fs::remove_dir_all()`, this removes the entries one at a time so that injected
+    /// errors are honored, so it performs the operation synchronously when called, and returns a
+    /// future which is already complete.
+    fn remove_dir_all<P: AsRef<Path>>(&mut self, path: P) -> FsFuture<()> {
+        async_fs::ready(Fs::remove_dir_all(self, path))
+    }
+
+    fn remove_file<P: AsRef<Path>>(&mut self, path: P) -> FsFuture<()> {
+        match self.change_writable_path(path) {
+            Ok(path) => {
+                self.track_nodes_async(&[&path], Box::pin(tokio::fs::remove_file(path.clone())))
+            }
+            Err(e) => async_fs::ready(Err(e)),
+        }
+    }
+
+    fn rename<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, from: P, to: Q) -> FsFuture<()> {
+        match self
+            .change_writable_path(from)
+            .and_then(|from| Ok((from, self.change_writable_path(to)?)))
+        {
+            Ok((from, to)) => self.track_nodes_async(
+                &[&from, &to],
+                Box::pin(tokio::fs::rename(from.clone(), to.clone())),
+            ),
+            Err(e) => async_fs::ready(Err(e)),
+        }
+    }
+
+    fn set_permissions<P: AsRef<Path>>(&mut self, path: P, perm: fs::Permissions) -> FsFuture<()> {
+        match self.change_writable_path(path) {
+            Ok(path) => Box::pin(tokio::fs::set_permissions(path, perm)),
+            Err(e) => async_fs::ready(Err(e)),
+        }
+    }
+
+    fn symlink_metadata<P: AsRef<Path>>(&self, path: P) -> FsFuture<fs::Metadata> {
+        self.counters.metadata.fetch_add(1, Ordering::Relaxed);
+        match self.change_link_path(path) {
+            Ok(path) => Box::pin(tokio::fs::symlink_metadata(path)),
+            Err(e) => async_fs::ready(Err(e)),
+        }
+    }
+
+    fn write<P: AsRef<Path>, C: AsRef<[u8]>>(&mut self, path: P, contents: C) -> FsFuture<()> {
+        self.counters.write.fetch_add(1, Ordering::Relaxed);
+        let path = self.change_writable_path(path).and_then(|path| {
+            self.check_can_create(&path)?;
+            Ok(path)
+        });
+        match path {
+            Ok(path) => {
+                let mut translated = Vec::new();
+                self.line_ending
+                    .append_translated(contents.as_ref(), &mut translated);
+                self.track_nodes_async(
+                    &[&path],
+                    Box::pin(tokio::fs::write(path.clone(), translated)),
+                )
+            }
+            Err(e) => async_fs::ready(Err(e)),
+        }
+    }
+
+    fn exists<P: AsRef<Path>>(&self, path: P) -> FsFuture<bool> {
+        match self.resolve_path(path) {
+            Ok(path) => Box::pin(tokio::fs::try_exists(path)),
+            Err(_) => async_fs::ready(Ok(false)),
+        }
     }
 }
@@ -16,6 +16,13 @@
 //! * [`TempFs`](fs/trait.TempFs.html) for performing filesystem access in a `chroot`-like sandbox
 //!   isolated from the rest of the filesystem
 //!
+//! When the `async` feature is enabled, [`AsyncFs`](fs/trait.AsyncFs.html) provides asynchronous
+//! filesystem access, implemented using Tokio by [`TokioFs`](fs/struct.TokioFs.html) and also
+//! implemented by `TempFs`.
+//!
+//! When the `testing` feature is enabled, the [`testing`](testing/index.html) module provides
+//! assertion helpers for use with the simulated providers.
+//!
 //! Each provider trait can be used independently, however there is also the all-encompassing
 //! [`Io`](trait.Io.html) which provides access to all of them. If you have a variety of I/O
 //! dependencies, it might be easiest to create and pass around a single `&mut Io`.
@@ -52,17 +59,130 @@
 //! ```
 
 extern crate tempfile;
+#[cfg(feature = "async")]
+extern crate tokio;
 
+use std::cell::Cell;
 use std::io;
 
 pub mod env;
 pub mod fs;
+mod paths;
 pub mod std_streams;
+#[cfg(feature = "testing")]
+pub mod testing;
 
-pub use env::{Env, NativeEnv, SimulatedEnv};
+pub use env::{Env, NativeEnv, Platform, SimulatedEnv};
 pub use fs::{Fs, NativeFs, OpenOptions, TempFs};
 pub use std_streams::{NativeStdStreams, SimulatedStdStreams, StdStreams};
 
+thread_local! {
+    static REAL_IO_DENIED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Denies the use of native providers on the current thread, until
+/// [`allow_real_io()`](fn.allow_real_io.html) is called.
+///
+/// While real I/O is denied, constructing a `NativeIo` or `NativeStdStreams`, or calling any method
+/// of a `NativeEnv` or `NativeFs` (which are unit structs, and so can't intercept construction),
+/// panics. Calling this at the start of a test catches code which accidentally uses the real
+/// system instead of a simulated provider.
+///
+/// # Examples
+///
+/// ```should_panic
+/// use io_providers::{Env, NativeEnv};
+///
+/// io_providers::deny_real_io();
+/// let _ = NativeEnv.current_dir(); // Panics
+/// ```
+pub fn deny_real_io() {
+    REAL_IO_DENIED.with(|denied| denied.set(true));
+}
+
+/// Allows the use of native providers on the current thread again, after a call to
+/// [`deny_real_io()`](fn.deny_real_io.html).
+pub fn allow_real_io() {
+    REAL_IO_DENIED.with(|denied| denied.set(false));
+}
+
+/// Panics if real I/O has been denied on the current thread.
+fn check_real_io_allowed(provider: &str) {
+    if REAL_IO_DENIED.with(|denied| denied.get()) {
+        panic!(
+            "{} was used while real I/O is denied on this thread (see \
+             io_providers::deny_real_io()); use a simulated provider instead",
+            provider
+        );
+    }
+}
+
+/// Line ending translation applied to text written to simulated providers.
+///
+/// This makes it possible to deterministically model platforms which translate line endings
+/// (e.g. Windows `\r\n`) on any host.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    /// Every `\r\n` is written as `\n`.
+    Lf,
+    /// Every `\n` not already preceded by `\r` is written as `\r\n`.
+    CrLf,
+    /// Data is written byte-for-byte, without any translation. This is the default.
+    #[default]
+    Passthrough,
+}
+
+impl LineEnding {
+    /// Appends `buf` to `out`, translating line endings. Line endings which are split across
+    /// consecutive calls (e.g. `\r` at the end of one buffer and `\n` at the start of the next)
+    /// are handled correctly.
+    fn append_translated(self, buf: &[u8], out: &mut Vec<u8>) {
+        for &b in buf {
+            match self {
+                LineEnding::Lf if b == b'\n' && out.last() == Some(&b'\r') => {
+                    let _ = out.pop();
+                }
+                LineEnding::CrLf if b == b'\n' && out.last() != Some(&b'\r') => {
+                    out.push(b'\r');
+                }
+                _ => {}
+            }
+            out.push(b);
+        }
+    }
+
+    /// Like [`append_translated()`](#method.append_translated), but treats `out` as a sequence of
+    /// UTF-16 code units, converted from and to bytes using `from_bytes` and `to_bytes`. `out`
+    /// must hold a whole number of code units before the first call; code units which are split
+    /// across consecutive calls are handled correctly.
+    fn append_translated_utf16(
+        self,
+        buf: &[u8],
+        out: &mut Vec<u8>,
+        from_bytes: fn([u8; 2]) -> u16,
+        to_bytes: fn(u16) -> [u8; 2],
+    ) {
+        let unit_before = |out: &[u8], end: usize| from_bytes([out[end - 2], out[end - 1]]);
+        for &b in buf {
+            out.push(b);
+            let len = out.len();
+            if !len.is_multiple_of(2) || unit_before(out, len) != u16::from(b'\n') {
+                continue;
+            }
+            let after_cr = len >= 4 && unit_before(out, len - 2) == u16::from(b'\r');
+            match self {
+                LineEnding::Lf if after_cr => {
+                    let _ = out.drain(len - 4..len - 2);
+                }
+                LineEnding::CrLf if !after_cr => {
+                    let _ = out.splice(len - 2..len - 2, to_bytes(u16::from(b'\r')));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
 /// Provides access to the process environment, filesystem, and standard streams.
 ///
 /// See [`env::Env`](env/trait.Env.html),
@@ -92,6 +212,73 @@ pub trait Io {
 
     /// Gets a mutable reference to the [`std_streams::StdStreams`](std_streams/trait.StdStreams.html).
     fn std_streams(&mut self) -> &mut Self::S;
+
+    /// Gets a locked handle to the standard output stream, like `io::stdout().lock()`.
+    ///
+    /// See [`StdStreams::lock_output()`](std_streams/trait.StdStreams.html#method.lock_output) for
+    /// more information.
+    fn lock_output<'a>(&'a mut self) -> Box<dyn io::Write + 'a> {
+        self.std_streams().lock_output()
+    }
+}
+
+/// Runs `f` with the given provider, returning its result.
+///
+/// This standardizes the pattern of injecting an `Io` provider into code which is shared between
+/// a program and its tests. See [`run_native()`](fn.run_native.html) and
+/// [`run_simulated()`](fn.run_simulated.html) for wrappers which create the provider as well.
+///
+/// # Examples
+///
+/// ```
+/// extern crate io_providers;
+///
+/// use std::io::Write;
+/// use io_providers::{Fs, Io, SimulatedIo, StdStreams};
+///
+/// /// Reports whether the root directory exists.
+/// fn check_root<I: Io>(io: &mut I) -> bool {
+///     let exists = io.fs().exists("/");
+///     writeln!(io.std_streams().output(), "root exists: {}", exists).unwrap();
+///     exists
+/// }
+///
+/// fn main() {
+///     // The same function can be run with the native system...
+///     assert!(io_providers::run_native(check_root));
+///
+///     // ...or with a simulated one, which is returned afterward so that it can be inspected
+///     let (exists, mut io) = io_providers::run_simulated(check_root).unwrap();
+///     assert!(exists);
+///     assert_eq!(b"root exists: true\n", io.std_streams().read_output());
+///
+///     // A provider which has been configured in advance can be used with `run_with()`
+///     let mut io = SimulatedIo::new().unwrap();
+///     io.fs_mut().write("/file.txt", "contents").unwrap();
+///     assert!(io_providers::run_with(&mut io, |io| io.fs().exists("/file.txt")));
+/// }
+/// ```
+pub fn run_with<I: Io, R, F: FnOnce(&mut I) -> R>(io: &mut I, f: F) -> R {
+    f(io)
+}
+
+/// Runs `f` with a new [`NativeIo`](struct.NativeIo.html), returning its result.
+///
+/// See [`run_with()`](fn.run_with.html) for more information.
+pub fn run_native<R, F: FnOnce(&mut NativeIo) -> R>(f: F) -> R {
+    run_with(&mut NativeIo::new(), f)
+}
+
+/// Runs `f` with a new [`SimulatedIo`](struct.SimulatedIo.html), returning its result along with
+/// the provider so that its state (e.g. captured output) can be inspected afterward.
+///
+/// Returns an error if the simulated provider can't be created.
+///
+/// See [`run_with()`](fn.run_with.html) for more information.
+pub fn run_simulated<R, F: FnOnce(&mut SimulatedIo) -> R>(f: F) -> io::Result<(R, SimulatedIo)> {
+    let mut io = SimulatedIo::new()?;
+    let result = run_with(&mut io, f);
+    Ok((result, io))
 }
 
 /// `Io` implementation using the native system.
@@ -107,12 +294,75 @@ pub struct NativeIo {
 impl NativeIo {
     /// Creates a new `LocalIoProvider`.
     pub fn new() -> NativeIo {
+        check_real_io_allowed("NativeIo");
         NativeIo {
             env: env::NativeEnv,
             fs: fs::NativeFs,
             stream: std_streams::NativeStdStreams::new(),
         }
     }
+
+    /// Replaces the standard input stream with `input`, while the environment, filesystem and
+    /// other streams stay native.
+    ///
+    /// See [`NativeStdStreams::with_input()`](std_streams/struct.NativeStdStreams.html#method.with_input).
+    pub fn with_input(mut self, input: Box<dyn io::Read + Send>) -> NativeIo {
+        self.stream = self.stream.with_input(input);
+        self
+    }
+
+    /// Replaces the standard output stream with `output`, while the environment, filesystem and
+    /// other streams stay native. This is a middle ground between `NativeIo` and `SimulatedIo`,
+    /// e.g. for capturing the output of a lightweight integration test.
+    ///
+    /// See [`NativeStdStreams::with_output()`](std_streams/struct.NativeStdStreams.html#method.with_output).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs::File;
+    /// use std::io::Write;
+    /// use io_providers::{Io, NativeIo, StdStreams};
+    ///
+    /// let log = File::create(std::env::temp_dir().join("io-providers-example.log")).unwrap();
+    /// let mut io = NativeIo::new().with_output(Box::new(log));
+    /// writeln!(io.std_streams().output(), "written to the log file").unwrap();
+    /// ```
+    pub fn with_output(mut self, output: Box<dyn io::Write + Send>) -> NativeIo {
+        self.stream = self.stream.with_output(output);
+        self
+    }
+
+    /// Replaces the standard error stream with `error`, while the environment, filesystem and
+    /// other streams stay native.
+    ///
+    /// See [`NativeStdStreams::with_error()`](std_streams/struct.NativeStdStreams.html#method.with_error).
+    pub fn with_error(mut self, error: Box<dyn io::Write + Send>) -> NativeIo {
+        self.stream = self.stream.with_error(error);
+        self
+    }
+
+    /// Gets a buffered handle to the standard output stream.
+    ///
+    /// See [`NativeStdStreams`](std_streams/struct.NativeStdStreams.html) for when buffered data
+    /// is flushed.
+    pub fn buffered_output(&mut self) -> &mut dyn io::Write {
+        self.stream.buffered_output()
+    }
+
+    /// Gets a buffered handle to the standard error stream.
+    ///
+    /// See [`NativeStdStreams::buffered_error()`](std_streams/struct.NativeStdStreams.html#method.buffered_error).
+    pub fn buffered_error(&mut self) -> &mut dyn io::Write {
+        self.stream.buffered_error()
+    }
+
+    /// Flushes any data written to the buffered output and error streams.
+    ///
+    /// See [`NativeStdStreams::flush_all()`](std_streams/struct.NativeStdStreams.html#method.flush_all).
+    pub fn flush_all(&mut self) -> io::Result<()> {
+        self.stream.flush_all()
+    }
 }
 
 impl Io for NativeIo {
@@ -159,6 +409,88 @@ impl SimulatedIo {
             stream: std_streams::SimulatedStdStreams::new(),
         })
     }
+
+    /// Creates a new `SimulatedIo` from providers which have already been configured, e.g. by
+    /// separate helpers.
+    ///
+    /// The providers don't share any state, so nothing needs to be reconciled when combining
+    /// them: in particular, the `TempFs` resolves relative paths from its root rather than from
+    /// the current working directory of the `SimulatedEnv`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use io_providers::{Env, Fs, Io, SimulatedEnv, SimulatedIo, SimulatedStdStreams, TempFs};
+    ///
+    /// let mut env = SimulatedEnv::new();
+    /// env.set_var("MODE", "test");
+    /// let mut fs = TempFs::new().unwrap();
+    /// fs.write("/config.txt", "verbose").unwrap();
+    ///
+    /// let io = SimulatedIo::from_parts(env, fs, SimulatedStdStreams::new());
+    /// assert_eq!("test", io.env().var("MODE").unwrap());
+    /// assert_eq!("verbose", io.fs().read_to_string("/config.txt").unwrap());
+    /// ```
+    pub fn from_parts(
+        env: env::SimulatedEnv,
+        fs: fs::TempFs,
+        stream: std_streams::SimulatedStdStreams,
+    ) -> SimulatedIo {
+        SimulatedIo { env, fs, stream }
+    }
+
+    /// Resets the environment, filesystem and standard streams to the state of a new
+    /// `SimulatedIo`, e.g. between the phases of a test which shares one provider.
+    ///
+    /// Configuration (such as the node limit of the filesystem, or the line ending of the
+    /// streams) is preserved. See
+    /// [`SimulatedEnv::reset()`](env/struct.SimulatedEnv.html#method.reset),
+    /// [`TempFs::reset()`](fs/struct.TempFs.html#method.reset) and
+    /// [`SimulatedStdStreams::reset()`](std_streams/struct.SimulatedStdStreams.html#method.reset)
+    /// for exactly what is cleared. The filesystem is reset first; if it couldn't be emptied, an
+    /// error is returned and the environment and streams are left unchanged.
+    pub fn reset(&mut self) -> io::Result<()> {
+        self.fs.reset()?;
+        self.env.reset();
+        self.stream.reset();
+        Ok(())
+    }
+
+    /// Consumes the `SimulatedIo`, returning its environment, filesystem and standard streams,
+    /// e.g. for inspection after a test.
+    ///
+    /// Note that the temporary filesystem is deleted once the returned `TempFs` is dropped.
+    pub fn into_parts(
+        self,
+    ) -> (
+        env::SimulatedEnv,
+        fs::TempFs,
+        std_streams::SimulatedStdStreams,
+    ) {
+        (self.env, self.fs, self.stream)
+    }
+
+    /// Gets a buffered handle to the standard output stream, which captures data immediately.
+    ///
+    /// See [`SimulatedStdStreams::buffered_output()`](std_streams/struct.SimulatedStdStreams.html#method.buffered_output).
+    pub fn buffered_output(&mut self) -> &mut dyn io::Write {
+        self.stream.buffered_output()
+    }
+
+    /// Gets a buffered handle to the standard error stream, which captures data immediately.
+    ///
+    /// See [`SimulatedStdStreams::buffered_error()`](std_streams/struct.SimulatedStdStreams.html#method.buffered_error).
+    pub fn buffered_error(&mut self) -> &mut dyn io::Write {
+        self.stream.buffered_error()
+    }
+
+    /// Flushes the standard output and error streams, which is counted by
+    /// [`SimulatedStdStreams::output_flush_count()`](std_streams/struct.SimulatedStdStreams.html#method.output_flush_count)
+    /// and
+    /// [`SimulatedStdStreams::error_flush_count()`](std_streams/struct.SimulatedStdStreams.html#method.error_flush_count).
+    pub fn flush_all(&mut self) -> io::Result<()> {
+        self.stream.flush_all()
+    }
 }
 
 impl Io for SimulatedIo {
@@ -0,0 +1,108 @@
+//! Path manipulation helpers shared by the providers.
+
+use std::ffi::OsString;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use fs::Fs;
+
+/// The maximum number of symbolic links followed by `resolve_symlinks()`, matching Linux.
+const MAX_SYMLINKS: usize = 40;
+
+/// Lexically normalizes a path by removing `.` components and resolving `..` components against
+/// the preceding component, without touching the filesystem.
+///
+/// `..` components which would go above the root are dropped; leading `..` components of a
+/// relative path are kept.
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match result.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    result.pop();
+                }
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => {}
+                _ => result.push(".."),
+            },
+            other => result.push(other.as_os_str()),
+        }
+    }
+    result
+}
+
+/// Resolves every symbolic link in `path` by inspecting it through `fs`, like
+/// `std::fs::canonicalize()` but without accessing the real filesystem directly.
+///
+/// `..` components are resolved after the links preceding them, as the OS does. Each component of
+/// the path must exist; an error is returned otherwise, or if more than `MAX_SYMLINKS` links are
+/// followed (e.g. because of a cycle).
+pub(crate) fn resolve_symlinks<F: Fs>(fs: &F, path: &Path) -> io::Result<PathBuf> {
+    let mut resolved = PathBuf::new();
+    // The components left to resolve, in reverse order
+    let mut pending: Vec<OsString> = Vec::new();
+    push_components(path, &mut resolved, &mut pending);
+    let mut links = 0;
+    while let Some(name) = pending.pop() {
+        if name == "." {
+            continue;
+        } else if name == ".." {
+            resolved.pop();
+            continue;
+        }
+        let candidate = resolved.join(&name);
+        if !fs.symlink_metadata(&candidate)?.file_type().is_symlink() {
+            resolved = candidate;
+            continue;
+        }
+        links += 1;
+        if links > MAX_SYMLINKS {
+            return Err(io::Error::other(format!(
+                "Too many levels of symbolic links: {}",
+                path.display()
+            )));
+        }
+        let target = fs.read_link(&candidate)?;
+        if target.is_absolute() {
+            resolved = PathBuf::new();
+        }
+        push_components(&target, &mut resolved, &mut pending);
+    }
+    Ok(resolved)
+}
+
+/// Pushes the prefix and root of `path` onto `resolved`, and its remaining components onto
+/// `pending` so that they are popped in order.
+fn push_components(path: &Path, resolved: &mut PathBuf, pending: &mut Vec<OsString>) {
+    let start = pending.len();
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => resolved.push(component.as_os_str()),
+            other => pending.push(other.as_os_str().to_os_string()),
+        }
+    }
+    pending[start..].reverse();
+}
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+    use super::normalize;
+    use std::path::Path;
+
+    #[test]
+    fn normalize__cur_and_parent_dirs__resolved() {
+        assert_eq!(Path::new("/a/c"), normalize(Path::new("/a/./b/../c")));
+    }
+
+    #[test]
+    fn normalize__parent_of_root__stays_at_root() {
+        assert_eq!(Path::new("/a"), normalize(Path::new("/../../a")));
+    }
+
+    #[test]
+    fn normalize__leading_parent_dirs_of_relative_path__kept() {
+        assert_eq!(Path::new("../../b"), normalize(Path::new("../a/../../b")));
+    }
+}
@@ -29,6 +29,31 @@
 //!     passthrough(&mut real_streams);
 //! }
 //! ```
+//!
+//! The streams returned by [`StdStreams`](trait.StdStreams.html) implement the `std::io` traits
+//! directly, so they can be handed to existing code which accepts `io::Read` or `io::Write`:
+//!
+//! ```
+//! extern crate io_providers;
+//!
+//! use std::io::Write;
+//! use io_providers::std_streams::{SimulatedStdStreams, StdStreams};
+//!
+//! fn greet(out: &mut dyn Write) {
+//!     writeln!(out, "hello").unwrap();
+//! }
+//!
+//! fn greet_generic<W: Write>(mut out: W) {
+//!     writeln!(out, "hi").unwrap();
+//! }
+//!
+//! fn main() {
+//!     let mut streams = SimulatedStdStreams::new();
+//!     greet(streams.output());
+//!     greet_generic(streams.output());
+//!     assert_eq!("hello\nhi\n", ::std::str::from_utf8(streams.read_output()).unwrap());
+//! }
+//! ```
 
 mod native;
 mod simulated;
@@ -36,16 +61,45 @@ mod simulated;
 use std::io;
 
 pub use self::native::NativeStdStreams;
-pub use self::simulated::SimulatedStdStreams;
+pub use self::simulated::{Encoding, SimulatedStdStreams};
 
 /// Provides access to input, output and error streams.
 pub trait StdStreams {
     /// Gets the input stream.
-    fn input(&mut self) -> &mut io::Read;
+    fn input(&mut self) -> &mut dyn io::Read;
 
     /// Gets the output stream.
-    fn output(&mut self) -> &mut io::Write;
+    fn output(&mut self) -> &mut dyn io::Write;
 
     /// Gets the error stream.
-    fn error(&mut self) -> &mut io::Write;
+    fn error(&mut self) -> &mut dyn io::Write;
+
+    /// Gets a locked handle to the output stream, for code written against the
+    /// `io::stdout().lock()` pattern of batching writes.
+    ///
+    /// `NativeStdStreams` returns a real `StdoutLock` (unless the output stream has been replaced),
+    /// so writes from other threads can't be interleaved while the handle is alive. The default
+    /// implementation, which is used by `SimulatedStdStreams`, returns the output stream itself: it
+    /// offers no mutual exclusion beyond the mutable borrow of the provider.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use std::io::Write;
+    /// use io_providers::{SimulatedStdStreams, StdStreams};
+    ///
+    /// fn report<S: StdStreams>(streams: &mut S, lines: &[&str]) {
+    ///     let mut out = streams.lock_output();
+    ///     for line in lines {
+    ///         writeln!(out, "{}", line).unwrap();
+    ///     }
+    /// }
+    ///
+    /// let mut streams = SimulatedStdStreams::new();
+    /// report(&mut streams, &["a", "b"]);
+    /// assert_eq!(b"a\nb\n", streams.read_output());
+    /// ```
+    fn lock_output<'a>(&'a mut self) -> Box<dyn io::Write + 'a> {
+        Box::new(self.output())
+    }
 }
@@ -1,12 +1,33 @@
 use std::io;
+use std::io::Write;
 use std_streams::StdStreams;
 
 /// Handles for the standard input streams of a process, using
 /// [`std::io`](https://doc.rust-lang.org/stable/std/io/).
+///
+/// In addition to the unbuffered streams provided through [`StdStreams`](trait.StdStreams.html),
+/// buffered output and error streams are available via
+/// [`buffered_output()`](#method.buffered_output) and [`buffered_error()`](#method.buffered_error).
+/// Data written to a buffered stream is automatically flushed:
+///
+///   * when the buffer is full;
+///   * before the corresponding unbuffered stream is returned by `output()` or `error()`, so that
+///     buffered and unbuffered writes are never reordered;
+///   * when [`flush_all()`](#method.flush_all) is called;
+///   * when the `NativeStdStreams` is dropped.
+///
+/// If an automatic flush fails, the error is returned by the next write to or flush of that
+/// stream (buffered or not), rather than being lost.
+///
+/// Individual streams can be replaced using [`with_input()`](#method.with_input),
+/// [`with_output()`](#method.with_output) and [`with_error()`](#method.with_error), e.g. to
+/// capture output in a buffer while other I/O stays native.
 pub struct NativeStdStreams {
-    input: io::Stdin,
-    output: io::Stdout,
-    error: io::Stderr,
+    input: Box<dyn io::Read + Send>,
+    output: io::BufWriter<RawStream>,
+    error: io::BufWriter<RawStream>,
+    // Whether `output` writes to the real standard output, which `lock_output()` can lock
+    output_is_stdout: bool,
 }
 
 impl NativeStdStreams {
@@ -16,12 +37,93 @@ impl NativeStdStreams {
     /// `std::io` functions return a new handle to their stream, so it's not possible to return
     /// `&mut` references to these handles unless we store them.
     pub fn new() -> Self {
+        ::check_real_io_allowed("NativeStdStreams");
         NativeStdStreams {
-            input: io::stdin(),
-            output: io::stdout(),
-            error: io::stderr(),
+            input: Box::new(io::stdin()),
+            output: io::BufWriter::new(RawStream::new(Box::new(io::stdout()))),
+            error: io::BufWriter::new(RawStream::new(Box::new(io::stderr()))),
+            output_is_stdout: true,
         }
     }
+
+    /// Replaces the input stream with `input`, leaving the other streams unchanged.
+    pub fn with_input(mut self, input: Box<dyn io::Read + Send>) -> Self {
+        self.input = input;
+        self
+    }
+
+    /// Replaces the output stream with `output`, leaving the other streams unchanged. Any data
+    /// buffered for the previous output stream is flushed to it first.
+    pub fn with_output(mut self, output: Box<dyn io::Write + Send>) -> Self {
+        self.output = io::BufWriter::new(RawStream::new(output));
+        self.output_is_stdout = false;
+        self
+    }
+
+    /// Replaces the error stream with `error`, leaving the other streams unchanged. Any data
+    /// buffered for the previous error stream is flushed to it first.
+    pub fn with_error(mut self, error: Box<dyn io::Write + Send>) -> Self {
+        self.error = io::BufWriter::new(RawStream::new(error));
+        self
+    }
+
+    /// Gets a buffered handle to the output stream.
+    pub fn buffered_output(&mut self) -> &mut dyn io::Write {
+        &mut self.output
+    }
+
+    /// Gets a buffered handle to the error stream.
+    pub fn buffered_error(&mut self) -> &mut dyn io::Write {
+        &mut self.error
+    }
+
+    /// Consumes the `NativeStdStreams`, returning an owned handle to the input stream.
+    pub fn into_input(self) -> impl io::Read + Send {
+        self.input
+    }
+
+    /// Consumes the `NativeStdStreams`, returning an owned handle to the output stream.
+    ///
+    /// Any buffered output is flushed first. This is useful for moving the stream into a struct
+    /// field or a type which is generic over `io::Write`. If the buffered output can't be
+    /// flushed, the error is returned and the stream is dropped, since the buffered data can't be
+    /// written to it in order.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use std::io::{self, Write};
+    /// use io_providers::NativeStdStreams;
+    ///
+    /// struct Logger<W: Write> {
+    ///     out: W,
+    /// }
+    ///
+    /// let streams = NativeStdStreams::new().with_output(Box::new(io::sink()));
+    /// let mut logger = Logger { out: streams.into_output().unwrap() };
+    /// writeln!(logger.out, "hello").unwrap();
+    /// ```
+    pub fn into_output(self) -> io::Result<impl io::Write + Send> {
+        self.output
+            .into_inner()
+            .map_err(io::IntoInnerError::into_error)
+    }
+
+    /// Consumes the `NativeStdStreams`, returning an owned handle to the error stream.
+    ///
+    /// Any buffered error output is flushed first, and an error is returned if that fails, as for
+    /// [`into_output()`](#method.into_output).
+    pub fn into_error(self) -> io::Result<impl io::Write + Send> {
+        self.error
+            .into_inner()
+            .map_err(io::IntoInnerError::into_error)
+    }
+
+    /// Flushes any data written to the buffered output and error streams.
+    pub fn flush_all(&mut self) -> io::Result<()> {
+        self.output.flush()?;
+        self.error.flush()
+    }
 }
 
 impl Default for NativeStdStreams {
@@ -31,15 +133,66 @@ impl Default for NativeStdStreams {
 }
 
 impl StdStreams for NativeStdStreams {
-    fn input(&mut self) -> &mut io::Read {
+    fn input(&mut self) -> &mut dyn io::Read {
         &mut self.input
     }
 
-    fn output(&mut self) -> &mut io::Write {
-        &mut self.output
+    fn output(&mut self) -> &mut dyn io::Write {
+        flushed(&mut self.output)
     }
 
-    fn error(&mut self) -> &mut io::Write {
-        &mut self.error
+    fn error(&mut self) -> &mut dyn io::Write {
+        flushed(&mut self.error)
+    }
+
+    fn lock_output<'a>(&'a mut self) -> Box<dyn io::Write + 'a> {
+        let output = flushed(&mut self.output);
+        // A pending flush error must be reported by the first write, which the real lock can't do
+        if self.output_is_stdout && output.flush_error.is_none() {
+            Box::new(io::stdout().lock())
+        } else {
+            Box::new(output)
+        }
+    }
+}
+
+/// Flushes `buffered`, keeping any error to be returned by the next write to the stream, and
+/// returns the unbuffered stream.
+fn flushed(buffered: &mut io::BufWriter<RawStream>) -> &mut RawStream {
+    if let Err(e) = buffered.flush() {
+        buffered.get_mut().flush_error = Some(e);
+    }
+    buffered.get_mut()
+}
+
+/// An unbuffered output or error stream, which returns the error of a failed automatic flush from
+/// its next write or flush.
+struct RawStream {
+    inner: Box<dyn io::Write + Send>,
+    flush_error: Option<io::Error>,
+}
+
+impl RawStream {
+    fn new(inner: Box<dyn io::Write + Send>) -> RawStream {
+        RawStream {
+            inner,
+            flush_error: None,
+        }
+    }
+}
+
+impl io::Write for RawStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.flush_error.take() {
+            Some(e) => Err(e),
+            None => self.inner.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.flush_error.take() {
+            Some(e) => Err(e),
+            None => self.inner.flush(),
+        }
     }
 }
@@ -1,7 +1,12 @@
 use std::collections::VecDeque;
+use std::fmt;
 use std::io;
 use std::io::{Read, Write};
+use std::path::Path;
+
+use fs::Fs;
 use std_streams::StdStreams;
+use LineEnding;
 
 /// Simulated handles for the standard input streams of a process.
 ///
@@ -9,11 +14,24 @@ use std_streams::StdStreams;
 /// [`write_input()`](std_streams/struct.SimulatedStdStreams.html#method.write_input), and output
 /// can be observed using [`read_output()`](std_streams/struct.SimulatedStdStreams.html#method.read_output)
 /// and [`read_error()`](std_streams/struct.SimulatedStdStreams.html#method.read_error).
+///
+/// The buffered streams returned by [`buffered_output()`](#method.buffered_output) and
+/// [`buffered_error()`](#method.buffered_error) write straight through to the same captured data
+/// as their unbuffered counterparts, but calls to `flush()` are still counted so that they can be
+/// asserted on.
+///
+/// Line endings written to the output and error streams can be translated using
+/// [`set_line_ending()`](#method.set_line_ending); by default data is captured byte-for-byte.
+///
+/// An interactive program can be driven turn by turn using
+/// [`add_repl_turn()`](#method.add_repl_turn).
 #[derive(Default)]
 pub struct SimulatedStdStreams {
     inputs: ChunkPipe,
-    output: Vec<u8>,
-    error: Vec<u8>,
+    output: CapturedOutput,
+    error: CapturedOutput,
+    repl_turns: VecDeque<(Vec<u8>, Vec<u8>)>,
+    repl_output_start: usize,
 }
 
 impl SimulatedStdStreams {
@@ -21,11 +39,28 @@ impl SimulatedStdStreams {
     pub fn new() -> SimulatedStdStreams {
         SimulatedStdStreams {
             inputs: ChunkPipe::new(),
-            output: Vec::new(),
-            error: Vec::new(),
+            output: CapturedOutput::new(),
+            error: CapturedOutput::new(),
+            repl_turns: VecDeque::new(),
+            repl_output_start: 0,
         }
     }
 
+    /// Resets these streams to the state of a new `SimulatedStdStreams`, so that they can be
+    /// reused.
+    ///
+    /// This discards queued input (including REPL turns) and the streaming state set by
+    /// [`push_input()`](#method.push_input) or [`close_input()`](#method.close_input), clears the
+    /// captured output and error data, and zeroes the read, write and flush counts. Configuration is
+    /// preserved: the line ending, the output and error encodings, and the maximum write lengths.
+    pub fn reset(&mut self) {
+        self.inputs = ChunkPipe::new();
+        self.output.reset();
+        self.error.reset();
+        self.repl_turns.clear();
+        self.repl_output_start = 0;
+    }
+
     /// Writes the provided buffer to the queue of buffers to be used when input is requested
     /// using [`StdStreams::input()`].
     ///
@@ -54,6 +89,164 @@ impl SimulatedStdStreams {
         self.inputs.write_all(input).unwrap();
     }
 
+    /// Enqueues a buffer of input like [`write_input()`](#method.write_input), and switches the
+    /// input stream into streaming mode, modelling a pipe which produces data over time.
+    ///
+    /// In streaming mode, a read when no input is queued fails with an error of kind
+    /// `io::ErrorKind::WouldBlock` (like a non-blocking pipe) rather than reporting the end of the
+    /// input, until [`close_input()`](#method.close_input) is called. Reads never actually block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use std::io::{self, Read};
+    /// use io_providers::{StdStreams, SimulatedStdStreams};
+    ///
+    /// let mut streams = SimulatedStdStreams::new();
+    /// let mut buf = [0; 16];
+    /// streams.push_input(b"partial");
+    /// assert_eq!(7, streams.input().read(&mut buf).unwrap());
+    /// assert_eq!(
+    ///     io::ErrorKind::WouldBlock,
+    ///     streams.input().read(&mut buf).unwrap_err().kind());
+    /// streams.close_input();
+    /// assert_eq!(0, streams.input().read(&mut buf).unwrap());
+    /// ```
+    pub fn push_input(&mut self, input: &[u8]) {
+        self.inputs.streaming = true;
+        self.write_input(input);
+    }
+
+    /// Closes the input stream, so that once all queued input has been read, reads report the end
+    /// of the input rather than failing with `io::ErrorKind::WouldBlock`.
+    ///
+    /// See [`push_input()`](#method.push_input) for more information.
+    pub fn close_input(&mut self) {
+        self.inputs.closed = true;
+    }
+
+    /// Reads the file at `path` from the `fs` provider and enqueues its contents, exactly as if
+    /// they were passed to [`write_input()`](#method.write_input).
+    ///
+    /// This makes it possible to keep input fixtures on disk, either in a
+    /// [`TempFs`](fs/struct.TempFs.html) or on the real filesystem using
+    /// [`NativeFs`](fs/struct.NativeFs.html). Note that the contents are enqueued as a single
+    /// buffer, so they are consumed by a single read.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use std::io::Read;
+    /// use io_providers::{Fs, StdStreams, SimulatedStdStreams, TempFs};
+    ///
+    /// let mut fs = TempFs::new().unwrap();
+    /// fs.write("/input.txt", "fixture data").unwrap();
+    ///
+    /// let mut streams = SimulatedStdStreams::new();
+    /// streams.write_input_from_file(&fs, "/input.txt").unwrap();
+    /// let mut input = String::new();
+    /// streams.input().read_to_string(&mut input).unwrap();
+    /// assert_eq!("fixture data", input);
+    /// ```
+    pub fn write_input_from_file<F: Fs, P: AsRef<Path>>(
+        &mut self,
+        fs: &F,
+        path: P,
+    ) -> io::Result<()> {
+        let input = fs.read(path)?;
+        self.write_input(&input);
+        Ok(())
+    }
+
+    /// Adds a turn to the script used to simulate an interactive session, such as a REPL.
+    ///
+    /// Each turn's `input` is only made available once the program has written `expected_prompt`
+    /// to the output stream. The check happens when [`StdStreams::input()`] is called while no
+    /// other input is queued: if the prompt appears anywhere in the output written since the
+    /// previous turn's input was released (after line ending translation), `input` is queued as a
+    /// single chunk, just as if it were passed to [`write_input()`](#method.write_input).
+    /// Otherwise, the program is asking for input before prompting for it, so
+    /// [`StdStreams::input()`] panics with a message naming the expected prompt.
+    ///
+    /// There are no timeouts: everything happens synchronously on the calling thread. Once the
+    /// script is exhausted, reads behave as they do without a script, so a program which reads
+    /// past the last turn sees the end of the input. Use
+    /// [`repl_turns_remaining()`](#method.repl_turns_remaining) to check that every turn was
+    /// consumed.
+    ///
+    /// [`StdStreams::input()`]: trait.StdStreams.html#tymethod.input
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use std::io::{Read, Write};
+    /// use io_providers::{StdStreams, SimulatedStdStreams};
+    ///
+    /// let mut streams = SimulatedStdStreams::new();
+    /// streams.add_repl_turn(b"1 + 1\n", b"> ");
+    ///
+    /// write!(streams.output(), "> ").unwrap();
+    /// let mut buf = [0; 16];
+    /// let len = streams.input().read(&mut buf).unwrap();
+    /// assert_eq!(b"1 + 1\n", &buf[..len]);
+    /// assert_eq!(0, streams.repl_turns_remaining());
+    /// ```
+    pub fn add_repl_turn(&mut self, input: &[u8], expected_prompt: &[u8]) {
+        self.repl_turns
+            .push_back((input.to_vec(), expected_prompt.to_vec()));
+    }
+
+    /// Gets the number of turns added using [`add_repl_turn()`](#method.add_repl_turn) whose input
+    /// has not yet been released.
+    pub fn repl_turns_remaining(&self) -> usize {
+        self.repl_turns.len()
+    }
+
+    /// Releases the input for the next scripted turn if input is about to be requested, panicking
+    /// if the expected prompt hasn't been written.
+    fn advance_repl(&mut self) {
+        if !self.inputs.items.is_empty() {
+            return;
+        }
+        let (input, prompt) = match self.repl_turns.pop_front() {
+            Some(turn) => turn,
+            None => return,
+        };
+        let written = &self.output.data[self.repl_output_start..];
+        let prompted = prompt.is_empty() || written.windows(prompt.len()).any(|w| w == &prompt[..]);
+        if !prompted {
+            panic!(
+                "Input was requested before the prompt {:?} was written (output since the previous turn: {:?})",
+                String::from_utf8_lossy(&prompt),
+                String::from_utf8_lossy(written)
+            );
+        }
+        self.repl_output_start = self.output.data.len();
+        self.write_input(&input);
+    }
+
+    /// Consumes the `SimulatedStdStreams`, returning an owned handle to the input stream.
+    ///
+    /// The handle yields the remaining queued input, one chunk per read. Inputs from turns added
+    /// with [`add_repl_turn()`](#method.add_repl_turn) which haven't started yet are discarded.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use std::io::Read;
+    /// use io_providers::SimulatedStdStreams;
+    ///
+    /// let mut streams = SimulatedStdStreams::new();
+    /// streams.write_input(b"input");
+    /// let mut input = streams.into_input();
+    /// let mut buf = Vec::new();
+    /// input.read_to_end(&mut buf).unwrap();
+    /// assert_eq!(b"input", &buf[..]);
+    /// ```
+    pub fn into_input(self) -> impl Read + Send {
+        self.inputs
+    }
+
     /// Gets the data which has been written to the output stream.
     ///
     /// ## Example
@@ -68,7 +261,7 @@ impl SimulatedStdStreams {
     /// assert_eq!("test1\ntest2", ::std::str::from_utf8(streams.read_output()).unwrap());
     /// ```
     pub fn read_output(&self) -> &[u8] {
-        &self.output[..]
+        &self.output.data[..]
     }
 
     /// Gets the data which has been written to the error stream.
@@ -85,29 +278,334 @@ impl SimulatedStdStreams {
     /// assert_eq!("test1\ntest2", ::std::str::from_utf8(streams.read_error()).unwrap());
     /// ```
     pub fn read_error(&self) -> &[u8] {
-        &self.error[..]
+        &self.error.data[..]
+    }
+
+    /// Sets the line ending translation applied to data written to the output and error streams.
+    ///
+    /// When a stream's encoding (see [`set_output_encoding()`](#method.set_output_encoding)) is
+    /// UTF-16, line endings are translated per code unit. Set the encoding before writing any data.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use std::io::Write;
+    /// use io_providers::{LineEnding, StdStreams, SimulatedStdStreams};
+    ///
+    /// let mut streams = SimulatedStdStreams::new();
+    /// streams.set_line_ending(LineEnding::CrLf);
+    /// writeln!(streams.output(), "test");
+    /// assert_eq!(b"test\r\n", streams.read_output());
+    /// ```
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.output.line_ending = line_ending;
+        self.error.line_ending = line_ending;
+    }
+
+    /// Sets the encoding used by [`read_output_string()`](#method.read_output_string) to decode the
+    /// data written to the output stream. The default is `Encoding::Utf8`.
+    pub fn set_output_encoding(&mut self, encoding: Encoding) {
+        self.output.encoding = encoding;
+    }
+
+    /// Sets the encoding used by [`read_error_string()`](#method.read_error_string) to decode the
+    /// data written to the error stream. The default is `Encoding::Utf8`.
+    pub fn set_error_encoding(&mut self, encoding: Encoding) {
+        self.error.encoding = encoding;
+    }
+
+    /// Gets the data which has been written to the output stream, decoded as a string using the
+    /// encoding set by [`set_output_encoding()`](#method.set_output_encoding).
+    ///
+    /// Returns an error of kind `io::ErrorKind::InvalidData` if the data isn't validly encoded.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use std::io::Write;
+    /// use io_providers::{StdStreams, SimulatedStdStreams};
+    /// use io_providers::std_streams::Encoding;
+    ///
+    /// let mut streams = SimulatedStdStreams::new();
+    /// streams.set_output_encoding(Encoding::Utf16Le);
+    /// streams.output().write_all(&[b'h', 0, b'i', 0]);
+    /// assert_eq!("hi", streams.read_output_string().unwrap());
+    /// ```
+    pub fn read_output_string(&self) -> io::Result<String> {
+        self.output.encoding.decode(&self.output.data)
+    }
+
+    /// Gets the data which has been written to the error stream, decoded as a string using the
+    /// encoding set by [`set_error_encoding()`](#method.set_error_encoding).
+    ///
+    /// Returns an error of kind `io::ErrorKind::InvalidData` if the data isn't validly encoded.
+    pub fn read_error_string(&self) -> io::Result<String> {
+        self.error.encoding.decode(&self.error.data)
+    }
+
+    /// Gets a buffered handle to the output stream.
+    ///
+    /// Data is captured immediately, exactly as if it were written to
+    /// [`StdStreams::output()`](trait.StdStreams.html#tymethod.output).
+    pub fn buffered_output(&mut self) -> &mut dyn Write {
+        &mut self.output
+    }
+
+    /// Gets a buffered handle to the error stream.
+    ///
+    /// Data is captured immediately, exactly as if it were written to
+    /// [`StdStreams::error()`](trait.StdStreams.html#tymethod.error).
+    pub fn buffered_error(&mut self) -> &mut dyn Write {
+        &mut self.error
+    }
+
+    /// Flushes the output and error streams.
+    pub fn flush_all(&mut self) -> io::Result<()> {
+        self.output.flush()?;
+        self.error.flush()
+    }
+
+    /// Gets the number of times the output stream has been flushed.
+    pub fn output_flush_count(&self) -> usize {
+        self.output.flush_count
+    }
+
+    /// Gets the number of times the error stream has been flushed.
+    pub fn error_flush_count(&self) -> usize {
+        self.error.flush_count
+    }
+
+    /// Sets the maximum number of bytes accepted by a single `write()` call on the output stream,
+    /// or `None` for no limit (the default).
+    ///
+    /// Each call accepts at most this many bytes and returns the number accepted, like a real
+    /// stream performing a short write, so code which calls `write()` directly must loop until the
+    /// whole buffer has been written (as `write_all()` does). A limit of zero causes every write of
+    /// a non-empty buffer to accept nothing, which `write_all()` reports as an error of kind
+    /// `io::ErrorKind::WriteZero`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use std::io::Write;
+    /// use io_providers::{StdStreams, SimulatedStdStreams};
+    ///
+    /// let mut streams = SimulatedStdStreams::new();
+    /// streams.set_output_max_write(Some(2));
+    /// assert_eq!(2, streams.output().write(b"hello").unwrap());
+    /// streams.output().write_all(b"llo").unwrap();
+    /// assert_eq!(b"hello", streams.read_output());
+    /// assert_eq!(3, streams.output_write_count());
+    /// ```
+    pub fn set_output_max_write(&mut self, max_write: Option<usize>) {
+        self.output.max_write = max_write;
+    }
+
+    /// Sets the maximum number of bytes accepted by a single `write()` call on the error stream,
+    /// or `None` for no limit (the default).
+    ///
+    /// See [`set_output_max_write()`](#method.set_output_max_write) for more information.
+    pub fn set_error_max_write(&mut self, max_write: Option<usize>) {
+        self.error.max_write = max_write;
+    }
+
+    /// Gets the number of `read()` calls made on the input stream.
+    pub fn input_read_count(&self) -> usize {
+        self.inputs.read_count
+    }
+
+    /// Gets the number of `write()` calls made on the output stream.
+    ///
+    /// Calls to `write_vectored()` aren't included; see
+    /// [`output_vectored_write_count()`](#method.output_vectored_write_count).
+    pub fn output_write_count(&self) -> usize {
+        self.output.write_count
+    }
+
+    /// Gets the number of `write()` calls made on the error stream.
+    ///
+    /// Calls to `write_vectored()` aren't included; see
+    /// [`error_vectored_write_count()`](#method.error_vectored_write_count).
+    pub fn error_write_count(&self) -> usize {
+        self.error.write_count
+    }
+
+    /// Gets the number of `write_vectored()` calls made on the output stream.
+    ///
+    /// The output and error streams implement `write_vectored()` by appending every buffer in
+    /// order (subject to the maximum write length, if any), so code using vectored writes doesn't
+    /// fall back to writing one buffer at a time.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use std::io::{IoSlice, Write};
+    /// use io_providers::{SimulatedStdStreams, StdStreams};
+    ///
+    /// let mut streams = SimulatedStdStreams::new();
+    /// let bufs = [IoSlice::new(b"key"), IoSlice::new(b"="), IoSlice::new(b"value")];
+    /// assert_eq!(9, streams.output().write_vectored(&bufs).unwrap());
+    /// assert_eq!(b"key=value", streams.read_output());
+    /// assert_eq!(1, streams.output_vectored_write_count());
+    /// assert_eq!(0, streams.output_write_count());
+    /// ```
+    pub fn output_vectored_write_count(&self) -> usize {
+        self.output.vectored_write_count
+    }
+
+    /// Gets the number of `write_vectored()` calls made on the error stream.
+    ///
+    /// See [`output_vectored_write_count()`](#method.output_vectored_write_count) for more
+    /// information.
+    pub fn error_vectored_write_count(&self) -> usize {
+        self.error.vectored_write_count
     }
 }
 
 impl StdStreams for SimulatedStdStreams {
-    fn input(&mut self) -> &mut Read {
+    fn input(&mut self) -> &mut dyn Read {
+        self.advance_repl();
         &mut self.inputs
     }
 
-    fn output(&mut self) -> &mut Write {
+    fn output(&mut self) -> &mut dyn Write {
         &mut self.output
     }
 
-    fn error(&mut self) -> &mut Write {
+    fn error(&mut self) -> &mut dyn Write {
         &mut self.error
     }
 }
 
+/// Text encodings which can be used to decode data written to a
+/// [`SimulatedStdStreams`](struct.SimulatedStdStreams.html).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    /// UTF-8. This is the default.
+    #[default]
+    Utf8,
+    /// UTF-16, little-endian.
+    Utf16Le,
+    /// UTF-16, big-endian.
+    Utf16Be,
+}
+
+impl Encoding {
+    fn decode(self, data: &[u8]) -> io::Result<String> {
+        let invalid =
+            |e: &dyn fmt::Display| io::Error::new(io::ErrorKind::InvalidData, e.to_string());
+        let from_bytes: fn([u8; 2]) -> u16 = match self {
+            Encoding::Utf8 => {
+                return String::from_utf8(data.to_vec()).map_err(|e| invalid(&e));
+            }
+            Encoding::Utf16Le => u16::from_le_bytes,
+            Encoding::Utf16Be => u16::from_be_bytes,
+        };
+        if !data.len().is_multiple_of(2) {
+            return Err(invalid(&"UTF-16 data has an odd number of bytes"));
+        }
+        let units = data.chunks(2).map(|c| from_bytes([c[0], c[1]]));
+        ::std::char::decode_utf16(units)
+            .collect::<Result<String, _>>()
+            .map_err(|e| invalid(&e))
+    }
+}
+
+/// A `Write` implementer which captures all written data and keeps track of how it was written.
+#[derive(Default)]
+struct CapturedOutput {
+    data: Vec<u8>,
+    encoding: Encoding,
+    line_ending: LineEnding,
+    flush_count: usize,
+    max_write: Option<usize>,
+    write_count: usize,
+    vectored_write_count: usize,
+}
+
+impl CapturedOutput {
+    /// Creates a new, empty `CapturedOutput`.
+    pub fn new() -> CapturedOutput {
+        CapturedOutput {
+            data: Vec::new(),
+            encoding: Encoding::Utf8,
+            line_ending: LineEnding::Passthrough,
+            flush_count: 0,
+            max_write: None,
+            write_count: 0,
+            vectored_write_count: 0,
+        }
+    }
+
+    /// Appends `buf` to the captured data, translating line endings. For UTF-16 encodings the
+    /// translation is applied to whole code units, so a `\n` is written as `\r\n` rather than
+    /// `\r` followed by the two bytes of the `\n` code unit.
+    fn append(&mut self, buf: &[u8]) {
+        match self.encoding {
+            Encoding::Utf8 => self.line_ending.append_translated(buf, &mut self.data),
+            Encoding::Utf16Le => self.line_ending.append_translated_utf16(
+                buf,
+                &mut self.data,
+                u16::from_le_bytes,
+                u16::to_le_bytes,
+            ),
+            Encoding::Utf16Be => self.line_ending.append_translated_utf16(
+                buf,
+                &mut self.data,
+                u16::from_be_bytes,
+                u16::to_be_bytes,
+            ),
+        }
+    }
+
+    /// Clears the captured data and counts, keeping the configuration.
+    fn reset(&mut self) {
+        self.data.clear();
+        self.flush_count = 0;
+        self.write_count = 0;
+        self.vectored_write_count = 0;
+    }
+}
+
+impl Write for CapturedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_count += 1;
+        let len = self.max_write.map_or(buf.len(), |max| buf.len().min(max));
+        self.append(&buf[..len]);
+        Ok(len)
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice]) -> io::Result<usize> {
+        self.vectored_write_count += 1;
+        let mut remaining = self.max_write.unwrap_or(usize::MAX);
+        let mut total = 0;
+        for buf in bufs {
+            let len = buf.len().min(remaining);
+            self.append(&buf[..len]);
+            total += len;
+            remaining -= len;
+            if remaining == 0 {
+                break;
+            }
+        }
+        Ok(total)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_count += 1;
+        Ok(())
+    }
+}
+
 /// A `Read` and `Write` implementer where data is written in chunks and each read consumes a
 /// single chunk.
 #[derive(Default)]
 struct ChunkPipe {
     items: VecDeque<Vec<u8>>,
+    // Whether an empty pipe which isn't closed reports `WouldBlock` rather than the end of input
+    streaming: bool,
+    closed: bool,
+    read_count: usize,
 }
 
 impl ChunkPipe {
@@ -115,14 +613,23 @@ impl ChunkPipe {
     pub fn new() -> ChunkPipe {
         ChunkPipe {
             items: VecDeque::new(),
+            streaming: false,
+            closed: false,
+            read_count: 0,
         }
     }
 }
 
 impl Read for ChunkPipe {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read_count += 1;
         if let Some(item) = self.items.pop_front() {
             io::Cursor::new(item).read(buf)
+        } else if self.streaming && !self.closed {
+            Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "no input is currently available",
+            ))
         } else {
             Ok(0)
         }
@@ -143,10 +650,13 @@ impl Write for ChunkPipe {
 }
 
 #[cfg(test)]
-#[allow(non_snake_case)]
+#[allow(non_snake_case, clippy::unused_io_amount, clippy::useless_vec)]
 mod tests {
-    use super::{ChunkPipe, SimulatedStdStreams, StdStreams};
+    use super::{ChunkPipe, Encoding, SimulatedStdStreams, StdStreams};
+    use fs::{Fs, TempFs};
+    use std::io;
     use std::io::{Read, Write};
+    use LineEnding;
 
     #[test]
     fn chunk_pipe__no_writes__reads_successfully() {
@@ -265,4 +775,282 @@ mod tests {
         assert_eq!(2, result2);
         assert_eq!(&[1, 2, 3, 4], actual);
     }
+
+    #[test]
+    fn provider__write_buffered_output__captured_with_unbuffered_output() {
+        let mut provider = SimulatedStdStreams::new();
+
+        provider.output().write_all(&[1, 2]).unwrap();
+        provider.buffered_output().write_all(&[3, 4]).unwrap();
+        provider.output().write_all(&[5]).unwrap();
+
+        assert_eq!(&[1, 2, 3, 4, 5], provider.read_output());
+    }
+
+    #[test]
+    fn provider__flush_all__flushes_counted() {
+        let mut provider = SimulatedStdStreams::new();
+
+        provider.buffered_output().flush().unwrap();
+        provider.flush_all().unwrap();
+
+        assert_eq!(2, provider.output_flush_count());
+        assert_eq!(1, provider.error_flush_count());
+    }
+
+    #[test]
+    fn provider__crlf_line_ending__newlines_translated() {
+        let mut provider = SimulatedStdStreams::new();
+        provider.set_line_ending(LineEnding::CrLf);
+
+        provider.output().write_all(b"a\nb\r\nc\r").unwrap();
+        provider.output().write_all(b"\nd\n").unwrap();
+        provider.error().write_all(b"e\n").unwrap();
+
+        assert_eq!(b"a\r\nb\r\nc\r\nd\r\n", provider.read_output());
+        assert_eq!(b"e\r\n", provider.read_error());
+    }
+
+    #[test]
+    fn provider__lf_line_ending__crlf_translated() {
+        let mut provider = SimulatedStdStreams::new();
+        provider.set_line_ending(LineEnding::Lf);
+
+        provider.output().write_all(b"a\r\nb\rc\r").unwrap();
+        provider.output().write_all(b"\n").unwrap();
+
+        assert_eq!(b"a\nb\rc\n", provider.read_output());
+    }
+
+    #[test]
+    fn provider__crlf_line_ending_utf16__code_units_translated() {
+        let mut provider = SimulatedStdStreams::new();
+        provider.set_line_ending(LineEnding::CrLf);
+        provider.set_output_encoding(Encoding::Utf16Le);
+        provider.set_error_encoding(Encoding::Utf16Be);
+
+        provider.output().write_all(&[b'a', 0, b'\n']).unwrap();
+        provider
+            .output()
+            .write_all(&[0, b'\r', 0, b'\n', 0])
+            .unwrap();
+        provider.error().write_all(&[0, b'\n']).unwrap();
+
+        assert_eq!("a\r\n\r\n", provider.read_output_string().unwrap());
+        assert_eq!("\r\n", provider.read_error_string().unwrap());
+    }
+
+    #[test]
+    fn provider__lf_line_ending_utf16__code_units_translated() {
+        let mut provider = SimulatedStdStreams::new();
+        provider.set_line_ending(LineEnding::Lf);
+        provider.set_output_encoding(Encoding::Utf16Le);
+
+        provider
+            .output()
+            .write_all(&[b'a', 0, b'\r', 0, b'\n', 0, 0x0a, 0x0d])
+            .unwrap();
+
+        assert_eq!(&[b'a', 0, b'\n', 0, 0x0a, 0x0d], provider.read_output());
+    }
+
+    #[test]
+    fn provider__passthrough_line_ending__newlines_untouched() {
+        let mut provider = SimulatedStdStreams::new();
+
+        provider.output().write_all(b"a\nb\r\n").unwrap();
+
+        assert_eq!(b"a\nb\r\n", provider.read_output());
+    }
+
+    #[test]
+    fn provider__utf16le_output__decoded_as_string() {
+        let mut provider = SimulatedStdStreams::new();
+        provider.set_output_encoding(Encoding::Utf16Le);
+        let encoded: Vec<u8> = "héllo ☃"
+            .encode_utf16()
+            .flat_map(|u| u.to_le_bytes().to_vec())
+            .collect();
+
+        provider.output().write_all(&encoded).unwrap();
+
+        assert_eq!("héllo ☃", provider.read_output_string().unwrap());
+        assert_eq!(&encoded[..], provider.read_output());
+    }
+
+    #[test]
+    fn provider__utf16be_error__decoded_as_string() {
+        let mut provider = SimulatedStdStreams::new();
+        provider.set_error_encoding(Encoding::Utf16Be);
+
+        provider.error().write_all(&[0, b'o', 0, b'k']).unwrap();
+
+        assert_eq!("ok", provider.read_error_string().unwrap());
+    }
+
+    #[test]
+    fn provider__invalid_utf8_output__string_is_error() {
+        let mut provider = SimulatedStdStreams::new();
+
+        provider.output().write_all(&[b'a', 0xff, b'b']).unwrap();
+        let result = provider.read_output_string();
+
+        assert_eq!(io::ErrorKind::InvalidData, result.unwrap_err().kind());
+        assert_eq!(&[b'a', 0xff, b'b'], provider.read_output());
+    }
+
+    #[test]
+    fn provider__odd_length_utf16_output__string_is_error() {
+        let mut provider = SimulatedStdStreams::new();
+        provider.set_output_encoding(Encoding::Utf16Le);
+
+        provider.output().write_all(&[b'a', 0, b'b']).unwrap();
+        let result = provider.read_output_string();
+
+        assert_eq!(io::ErrorKind::InvalidData, result.unwrap_err().kind());
+    }
+
+    #[test]
+    fn add_repl_turn__two_turns__inputs_released_after_prompts() {
+        let mut streams = SimulatedStdStreams::new();
+        streams.add_repl_turn(b"first\n", b"> ");
+        streams.add_repl_turn(b"second\n", b"> ");
+        let mut buf = [0; 16];
+
+        write!(streams.output(), "welcome\n> ").unwrap();
+        let len = streams.input().read(&mut buf).unwrap();
+        assert_eq!(b"first\n", &buf[..len]);
+
+        write!(streams.output(), "got first\n> ").unwrap();
+        let len = streams.input().read(&mut buf).unwrap();
+        assert_eq!(b"second\n", &buf[..len]);
+
+        assert_eq!(0, streams.repl_turns_remaining());
+        assert_eq!(0, streams.input().read(&mut buf).unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "before the prompt")]
+    fn add_repl_turn__input_before_second_prompt__panics() {
+        let mut streams = SimulatedStdStreams::new();
+        streams.add_repl_turn(b"first\n", b"> ");
+        streams.add_repl_turn(b"second\n", b"> ");
+        let mut buf = [0; 16];
+
+        write!(streams.output(), "> ").unwrap();
+        assert_eq!(6, streams.input().read(&mut buf).unwrap());
+        // The first prompt doesn't count towards the second turn
+        let _ = streams.input().read(&mut buf);
+    }
+
+    #[test]
+    fn write_input_from_file__fixture_file__read_as_input() {
+        let mut fs = TempFs::new().unwrap();
+        fs.write("fixture.txt", "line one\nline two\n").unwrap();
+        let mut streams = SimulatedStdStreams::new();
+        let mut buf = vec![0; 64];
+
+        streams.write_input_from_file(&fs, "fixture.txt").unwrap();
+        let len = streams.input().read(&mut buf).unwrap();
+
+        assert_eq!(b"line one\nline two\n", &buf[..len]);
+    }
+
+    #[test]
+    fn write_input_from_file__missing_file__not_found() {
+        let fs = TempFs::new().unwrap();
+        let mut streams = SimulatedStdStreams::new();
+
+        let result = streams.write_input_from_file(&fs, "missing.txt");
+
+        assert_eq!(io::ErrorKind::NotFound, result.unwrap_err().kind());
+    }
+
+    #[test]
+    fn write_vectored__three_slices__concatenated_in_one_call() {
+        let mut streams = SimulatedStdStreams::new();
+        let bufs = [
+            io::IoSlice::new(b"abc"),
+            io::IoSlice::new(b""),
+            io::IoSlice::new(b"def"),
+            io::IoSlice::new(b"gh"),
+        ];
+
+        let written = streams.error().write_vectored(&bufs).unwrap();
+
+        assert_eq!(8, written);
+        assert_eq!(b"abcdefgh", streams.read_error());
+        assert_eq!(1, streams.error_vectored_write_count());
+        assert_eq!(0, streams.error_write_count());
+        assert_eq!(0, streams.output_vectored_write_count());
+    }
+
+    #[test]
+    fn write_vectored__max_write_set__short_write_spans_slices() {
+        let mut streams = SimulatedStdStreams::new();
+        streams.set_output_max_write(Some(4));
+        let bufs = [io::IoSlice::new(b"abc"), io::IoSlice::new(b"def")];
+
+        let written = streams.output().write_vectored(&bufs).unwrap();
+
+        assert_eq!(4, written);
+        assert_eq!(b"abcd", streams.read_output());
+    }
+
+    #[test]
+    fn set_output_max_write__write_all_100_bytes_capped_at_10__ten_writes() {
+        let mut streams = SimulatedStdStreams::new();
+        let data = (0..100).collect::<Vec<u8>>();
+        streams.set_output_max_write(Some(10));
+
+        streams.output().write_all(&data).unwrap();
+
+        assert_eq!(10, streams.output_write_count());
+        assert_eq!(&data[..], streams.read_output());
+    }
+
+    #[test]
+    fn set_error_max_write__single_write__short_write_returned() {
+        let mut streams = SimulatedStdStreams::new();
+        streams.set_error_max_write(Some(3));
+
+        let result = streams.error().write(b"abcdef").unwrap();
+
+        assert_eq!(3, result);
+        assert_eq!(b"abc", streams.read_error());
+        assert_eq!(1, streams.error_write_count());
+        assert_eq!(0, streams.output_write_count());
+    }
+
+    #[test]
+    fn push_input__two_chunks_then_close__would_block_between_and_eof_after() {
+        let mut streams = SimulatedStdStreams::new();
+        let mut buf = [0; 16];
+
+        streams.push_input(b"first");
+        assert_eq!(5, streams.input().read(&mut buf).unwrap());
+        assert_eq!(
+            io::ErrorKind::WouldBlock,
+            streams.input().read(&mut buf).unwrap_err().kind()
+        );
+        streams.push_input(b"second");
+        let len = streams.input().read(&mut buf).unwrap();
+        assert_eq!(b"second", &buf[..len]);
+        streams.close_input();
+
+        assert_eq!(0, streams.input().read(&mut buf).unwrap());
+    }
+
+    #[test]
+    fn input_read_count__reads_until_eof__counts_each_read() {
+        let mut streams = SimulatedStdStreams::new();
+        streams.write_input(b"a");
+        streams.write_input(b"b");
+        let mut input = Vec::new();
+
+        streams.input().read_to_end(&mut input).unwrap();
+
+        assert_eq!(b"ab", &input[..]);
+        assert_eq!(3, streams.input_read_count());
+    }
 }
@@ -0,0 +1,311 @@
+//! Helpers for asserting on the state of simulated providers in tests.
+//!
+//! This module is only available when the `testing` feature is enabled.
+
+use std::path::Path;
+
+use env::Env;
+use fs::Fs;
+use std_streams::SimulatedStdStreams;
+
+/// The name of the environment variable which enables updating golden files in
+/// [`assert_matches_golden()`](fn.assert_matches_golden.html).
+pub const UPDATE_GOLDEN_VAR: &str = "UPDATE_GOLDEN";
+
+/// Chainable assertions on the data written to a
+/// [`SimulatedStdStreams`](../std_streams/struct.SimulatedStdStreams.html).
+///
+/// Each assertion panics with a descriptive message (including a line-by-line diff for equality
+/// assertions) if it fails, and otherwise returns `&Self` so that further assertions can be
+/// chained. Captured data is decoded as text using the encoding configured on the stream.
+///
+/// Processes run in-process against simulated streams don't have an exit code, so there is no
+/// equivalent to the `assert_exit_code()` offered by CLI testing crates; assert on the return
+/// value of the function under test instead.
+///
+/// # Examples
+///
+/// ```
+/// extern crate io_providers;
+///
+/// use std::io::Write;
+/// use io_providers::{SimulatedStdStreams, StdStreams};
+/// use io_providers::testing::StdStreamsAssertions;
+///
+/// fn run<S: StdStreams>(streams: &mut S) {
+///     writeln!(streams.output(), "working...").unwrap();
+///     writeln!(streams.output(), "done").unwrap();
+/// }
+///
+/// fn main() {
+///     let mut streams = SimulatedStdStreams::new();
+///     run(&mut streams);
+///
+///     streams
+///         .assert_output_contains("done")
+///         .assert_output_eq("working...\ndone\n")
+///         .assert_error_empty();
+/// }
+/// ```
+pub trait StdStreamsAssertions {
+    /// Asserts that the output stream contains `expected`.
+    fn assert_output_contains(&self, expected: &str) -> &Self;
+
+    /// Asserts that the output stream is exactly `expected`.
+    fn assert_output_eq(&self, expected: &str) -> &Self;
+
+    /// Asserts that nothing has been written to the output stream.
+    fn assert_output_empty(&self) -> &Self;
+
+    /// Asserts that the error stream contains `expected`.
+    fn assert_error_contains(&self, expected: &str) -> &Self;
+
+    /// Asserts that the error stream is exactly `expected`.
+    fn assert_error_eq(&self, expected: &str) -> &Self;
+
+    /// Asserts that nothing has been written to the error stream.
+    fn assert_error_empty(&self) -> &Self;
+}
+
+impl StdStreamsAssertions for SimulatedStdStreams {
+    fn assert_output_contains(&self, expected: &str) -> &Self {
+        assert_contains(
+            "output",
+            &decoded("output", self.read_output_string()),
+            expected,
+        );
+        self
+    }
+
+    fn assert_output_eq(&self, expected: &str) -> &Self {
+        assert_text_eq(
+            "output",
+            &decoded("output", self.read_output_string()),
+            expected,
+        );
+        self
+    }
+
+    fn assert_output_empty(&self) -> &Self {
+        if !self.read_output().is_empty() {
+            panic!(
+                "expected the output stream to be empty, but it was:\n{}",
+                String::from_utf8_lossy(self.read_output())
+            );
+        }
+        self
+    }
+
+    fn assert_error_contains(&self, expected: &str) -> &Self {
+        assert_contains(
+            "error",
+            &decoded("error", self.read_error_string()),
+            expected,
+        );
+        self
+    }
+
+    fn assert_error_eq(&self, expected: &str) -> &Self {
+        assert_text_eq(
+            "error",
+            &decoded("error", self.read_error_string()),
+            expected,
+        );
+        self
+    }
+
+    fn assert_error_empty(&self) -> &Self {
+        if !self.read_error().is_empty() {
+            panic!(
+                "expected the error stream to be empty, but it was:\n{}",
+                String::from_utf8_lossy(self.read_error())
+            );
+        }
+        self
+    }
+}
+
+/// Asserts that `actual` matches the contents of the golden file at `golden_path`, or updates the
+/// golden file if requested.
+///
+/// If the [`UPDATE_GOLDEN`](constant.UPDATE_GOLDEN_VAR.html) environment variable is set (to
+/// anything other than an empty string or `0`) according to `env`, `actual` is written to
+/// `golden_path` instead, so that running e.g. `UPDATE_GOLDEN=1 cargo test` with a `NativeEnv`
+/// and `NativeFs` rewrites the expected fixtures. Otherwise, this panics if the golden file
+/// can't be read, or with a line-by-line diff if its contents don't match.
+///
+/// # Examples
+///
+/// ```
+/// extern crate io_providers;
+///
+/// use io_providers::{Env, Fs, SimulatedEnv, TempFs};
+/// use io_providers::testing::assert_matches_golden;
+///
+/// fn main() {
+///     let mut env = SimulatedEnv::new();
+///     let mut fs = TempFs::new().unwrap();
+///
+///     env.set_var("UPDATE_GOLDEN", "1");
+///     assert_matches_golden(&env, &mut fs, "/expected.txt", b"report\n");
+///     assert_eq!(b"report\n".to_vec(), fs.read("/expected.txt").unwrap());
+///
+///     env.remove_var("UPDATE_GOLDEN");
+///     assert_matches_golden(&env, &mut fs, "/expected.txt", b"report\n");
+/// }
+/// ```
+pub fn assert_matches_golden<E: Env, F: Fs, P: AsRef<Path>>(
+    env: &E,
+    fs: &mut F,
+    golden_path: P,
+    actual: &[u8],
+) {
+    let golden_path = golden_path.as_ref();
+    let update = env
+        .var_os(UPDATE_GOLDEN_VAR)
+        .is_some_and(|value| !value.is_empty() && value != "0");
+    if update {
+        if let Err(e) = fs.write(golden_path, actual) {
+            panic!(
+                "the golden file {} could not be written: {}",
+                golden_path.display(),
+                e
+            );
+        }
+        return;
+    }
+
+    let expected = fs.read(golden_path).unwrap_or_else(|e| {
+        panic!(
+            "the golden file {} could not be read (set {}=1 to create it): {}",
+            golden_path.display(),
+            UPDATE_GOLDEN_VAR,
+            e
+        )
+    });
+    if expected != actual {
+        panic!(
+            "the data did not match the golden file {} (-expected +actual; set {}=1 to update \
+             it):\n{}",
+            golden_path.display(),
+            UPDATE_GOLDEN_VAR,
+            diff(
+                &String::from_utf8_lossy(&expected),
+                &String::from_utf8_lossy(actual)
+            )
+        );
+    }
+}
+
+fn decoded(stream: &str, text: ::std::io::Result<String>) -> String {
+    text.unwrap_or_else(|e| panic!("the {} stream could not be decoded: {}", stream, e))
+}
+
+fn assert_contains(stream: &str, actual: &str, expected: &str) {
+    if !actual.contains(expected) {
+        panic!(
+            "expected the {} stream to contain {:?}, but it was:\n{}",
+            stream, expected, actual
+        );
+    }
+}
+
+fn assert_text_eq(stream: &str, actual: &str, expected: &str) {
+    if actual != expected {
+        panic!(
+            "the {} stream did not match (-expected +actual):\n{}",
+            stream,
+            diff(expected, actual)
+        );
+    }
+}
+
+/// Produces a simple line-by-line diff of two strings, marking lines only in `expected` with `-`
+/// and lines only in `actual` with `+`.
+pub(crate) fn diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.split('\n').collect();
+    let actual: Vec<&str> = actual.split('\n').collect();
+    let mut result = String::new();
+    for i in 0..expected.len().max(actual.len()) {
+        match (expected.get(i), actual.get(i)) {
+            (Some(e), Some(a)) if e == a => result.push_str(&format!("  {:?}\n", e)),
+            (e, a) => {
+                if let Some(e) = e {
+                    result.push_str(&format!("- {:?}\n", e));
+                }
+                if let Some(a) = a {
+                    result.push_str(&format!("+ {:?}\n", a));
+                }
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+    use super::{assert_matches_golden, diff, StdStreamsAssertions};
+    use env::{Env, SimulatedEnv};
+    use fs::{Fs, TempFs};
+    use std_streams::{SimulatedStdStreams, StdStreams};
+
+    #[test]
+    fn assertions__matching_streams__chain_succeeds() {
+        let mut streams = SimulatedStdStreams::new();
+        write!(streams.output(), "a\nb").unwrap();
+
+        streams
+            .assert_output_contains("a\n")
+            .assert_output_eq("a\nb")
+            .assert_error_empty();
+    }
+
+    #[test]
+    #[should_panic(expected = "did not match")]
+    fn assert_output_eq__different_output__panics() {
+        let mut streams = SimulatedStdStreams::new();
+        write!(streams.output(), "a\nb").unwrap();
+
+        streams.assert_output_eq("a\nc");
+    }
+
+    #[test]
+    #[should_panic(expected = "to be empty")]
+    fn assert_error_empty__error_written__panics() {
+        let mut streams = SimulatedStdStreams::new();
+        write!(streams.error(), "oops").unwrap();
+
+        streams.assert_error_empty();
+    }
+
+    #[test]
+    fn diff__one_line_differs__marks_line() {
+        let result = diff("a\nb\nc", "a\nx\nc");
+
+        assert_eq!("  \"a\"\n- \"b\"\n+ \"x\"\n  \"c\"\n", result);
+    }
+
+    #[test]
+    fn assert_matches_golden__update_var_set__writes_golden_file() {
+        let mut env = SimulatedEnv::new();
+        let mut fs = TempFs::new().unwrap();
+        fs.write("golden.txt", "old").unwrap();
+        env.set_var("UPDATE_GOLDEN", "1");
+
+        assert_matches_golden(&env, &mut fs, "golden.txt", b"new");
+
+        assert_eq!(b"new".to_vec(), fs.read("golden.txt").unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "did not match the golden file")]
+    fn assert_matches_golden__mismatch_without_update_var__panics() {
+        let mut env = SimulatedEnv::new();
+        let mut fs = TempFs::new().unwrap();
+        fs.write("golden.txt", "line one\nline two\n").unwrap();
+        env.set_var("UPDATE_GOLDEN", "0");
+
+        assert_matches_golden(&env, &mut fs, "golden.txt", b"line one\nline 2\n");
+    }
+}
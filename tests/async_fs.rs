@@ -0,0 +1,164 @@
+#![cfg(feature = "async")]
+#![allow(non_snake_case)]
+
+extern crate io_providers;
+extern crate tokio;
+
+use std::fs;
+use std::future::Future;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use io_providers::fs::{AsyncFs, Fs, OpenOptions, TempFs, TokioFs};
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("Failed to create runtime")
+        .block_on(future)
+}
+
+#[test]
+fn async_fs__file_written__readable_by_sync_fs() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+
+    block_on(AsyncFs::write(&mut fs, "/test.txt", "contents")).expect("Failed to write test file");
+    let contents = Fs::read_to_string(&fs, "test.txt").expect("Failed to read test file");
+
+    assert_eq!("contents", contents);
+}
+
+#[test]
+fn async_fs__file_written_by_sync_fs__readable() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    Fs::write(&mut fs, "test.txt", "contents").expect("Failed to write test file");
+
+    let contents = block_on(AsyncFs::read(&fs, "test.txt")).expect("Failed to read test file");
+    let metadata = block_on(AsyncFs::metadata(&fs, "test.txt")).expect("Failed to get metadata");
+
+    assert_eq!(b"contents".to_vec(), contents);
+    assert_eq!(8, metadata.len());
+}
+
+#[test]
+fn async_fs__create_dir_all__creates_missing_parents() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+
+    block_on(AsyncFs::create_dir_all(&mut fs, "/a/b/c")).expect("Failed to create directories");
+
+    assert!(Fs::metadata(&fs, "a/b/c").unwrap().is_dir());
+}
+
+#[test]
+fn async_fs__max_nodes__count_updated_as_operations_complete() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    fs.set_max_nodes(Some(2));
+
+    block_on(AsyncFs::write(&mut fs, "one.txt", "1")).unwrap();
+    block_on(AsyncFs::write(&mut fs, "two.txt", "2")).unwrap();
+    let full = block_on(AsyncFs::write(&mut fs, "three.txt", "3"));
+    block_on(AsyncFs::remove_file(&mut fs, "one.txt")).unwrap();
+    block_on(AsyncFs::write(&mut fs, "three.txt", "3")).unwrap();
+
+    assert!(full.is_err());
+    assert_eq!(2, fs.file_count().unwrap());
+}
+
+fn set_accessed(fs: &TempFs, path: &str, accessed: SystemTime) {
+    fs::File::open(fs.path().join(path))
+        .unwrap()
+        .set_times(fs::FileTimes::new().set_accessed(accessed))
+        .unwrap();
+}
+
+#[test]
+fn async_fs__update_atime_disabled__read_preserves_accessed_time() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    let past = UNIX_EPOCH + Duration::from_secs(1_000_000);
+    Fs::write(&mut fs, "test.txt", "contents").unwrap();
+    set_accessed(&fs, "test.txt", past);
+
+    block_on(AsyncFs::read(&fs, "test.txt")).unwrap();
+    block_on(AsyncFs::read_to_string(&fs, "test.txt")).unwrap();
+
+    let accessed = Fs::metadata(&fs, "test.txt").unwrap().accessed().unwrap();
+    assert_eq!(past, accessed);
+}
+
+#[test]
+fn async_fs__update_atime_enabled__read_and_open_advance_accessed_time() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    let past = UNIX_EPOCH + Duration::from_secs(1_000_000);
+    fs.set_update_atime(true);
+    Fs::write(&mut fs, "read.txt", "contents").unwrap();
+    Fs::write(&mut fs, "open.txt", "contents").unwrap();
+    set_accessed(&fs, "read.txt", past);
+    set_accessed(&fs, "open.txt", past);
+
+    block_on(AsyncFs::read(&fs, "read.txt")).unwrap();
+    block_on(AsyncFs::open(
+        &mut fs,
+        "open.txt",
+        OpenOptions::new().read(true),
+    ))
+    .unwrap();
+
+    assert!(Fs::metadata(&fs, "read.txt").unwrap().accessed().unwrap() > past);
+    assert!(Fs::metadata(&fs, "open.txt").unwrap().accessed().unwrap() > past);
+}
+
+#[test]
+fn async_fs__remove_dir_all_with_injected_error__error_names_nested_path() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    Fs::create_dir_all(&mut fs, "a/b").unwrap();
+    Fs::write(&mut fs, "a/b/locked.txt", "").unwrap();
+    Fs::write(&mut fs, "a/other.txt", "").unwrap();
+    fs.inject_error("a/b/locked.txt", io::ErrorKind::PermissionDenied)
+        .unwrap();
+
+    let error = block_on(AsyncFs::remove_dir_all(&mut fs, "a")).unwrap_err();
+
+    assert_eq!(io::ErrorKind::PermissionDenied, error.kind());
+    assert!(error
+        .to_string()
+        .contains(&format!("{}", Path::new("a/b/locked.txt").display())));
+    assert!(Fs::exists(&fs, "a/b/locked.txt"));
+}
+
+#[test]
+fn async_fs__path_traverses_out_of_root__is_invalid() {
+    let fs = TempFs::new().expect("Failed to create new TempFs");
+
+    let result = block_on(AsyncFs::read(&fs, "../test.txt"));
+    let exists = block_on(AsyncFs::exists(&fs, "../test.txt")).unwrap();
+
+    assert!(result.is_err());
+    assert!(!exists);
+}
+
+#[test]
+fn async_fs__open_with_create__file_created_in_root() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+
+    let file = block_on(AsyncFs::open(
+        &mut fs,
+        "test.txt",
+        OpenOptions::new().write(true).create(true),
+    ));
+
+    assert!(file.is_ok());
+    assert!(fs.path().join("test.txt").exists());
+}
+
+#[test]
+fn tokio_fs__file_written__readable() {
+    let fs = TempFs::new().expect("Failed to create new TempFs");
+    let path = fs.path().join("test.txt");
+    let mut tokio_fs = TokioFs;
+
+    block_on(tokio_fs.write(&path, "contents")).expect("Failed to write test file");
+    let contents = block_on(tokio_fs.read_to_string(&path)).expect("Failed to read test file");
+
+    assert_eq!("contents", contents);
+}
@@ -0,0 +1,60 @@
+#![allow(non_snake_case)]
+
+extern crate io_providers;
+
+use std::path::Path;
+
+use io_providers::{
+    allow_real_io, deny_real_io, Env, Fs, NativeEnv, NativeFs, NativeIo, NativeStdStreams,
+    SimulatedEnv, SimulatedIo, SimulatedStdStreams, TempFs,
+};
+
+#[test]
+#[should_panic(expected = "NativeEnv was used while real I/O is denied")]
+fn deny_real_io__native_env_used__panics() {
+    deny_real_io();
+    let _ = NativeEnv.current_dir();
+}
+
+#[test]
+#[should_panic(expected = "NativeFs was used while real I/O is denied")]
+fn deny_real_io__native_fs_used__panics() {
+    deny_real_io();
+    let _ = NativeFs.exists("/");
+}
+
+#[test]
+#[should_panic(expected = "NativeStdStreams was used while real I/O is denied")]
+fn deny_real_io__native_std_streams_constructed__panics() {
+    deny_real_io();
+    let _ = NativeStdStreams::new();
+}
+
+#[test]
+#[should_panic(expected = "NativeIo was used while real I/O is denied")]
+fn deny_real_io__native_io_constructed__panics() {
+    deny_real_io();
+    let _ = NativeIo::new();
+}
+
+#[test]
+fn deny_real_io__simulated_providers_used__succeeds() {
+    deny_real_io();
+
+    let mut env = SimulatedEnv::new();
+    env.set_current_dir(Path::new("/foo")).unwrap();
+    let mut fs = TempFs::new().unwrap();
+    fs.write("test.txt", "contents").unwrap();
+    let _ = SimulatedStdStreams::new();
+    let _ = SimulatedIo::new().unwrap();
+
+    allow_real_io();
+}
+
+#[test]
+fn allow_real_io__after_deny__native_providers_usable() {
+    deny_real_io();
+    allow_real_io();
+
+    assert!(NativeFs.exists("/"));
+}
@@ -0,0 +1,126 @@
+#![allow(non_snake_case)]
+
+extern crate io_providers;
+
+use std::io;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use io_providers::{Env, Io, NativeIo, NativeStdStreams, StdStreams};
+
+/// A `Write` implementer whose data can be inspected after it has been moved into a stream.
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A `Write` implementer which fails every write.
+struct FailingWriter;
+
+impl Write for FailingWriter {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(io::Error::other("write failed"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn native_io__with_output__captures_output_while_env_stays_native() {
+    let output = SharedBuffer::default();
+    let mut io = NativeIo::new().with_output(Box::new(output.clone()));
+
+    let current_dir = io.env().current_dir().unwrap();
+    writeln!(io.std_streams().output(), "{}", current_dir.display()).unwrap();
+    io.std_streams().flush_all().unwrap();
+
+    assert_eq!(
+        format!("{}\n", current_dir.display()).into_bytes(),
+        *output.0.lock().unwrap()
+    );
+}
+
+#[test]
+fn native_io__with_error_and_input__streams_replaced() {
+    let error = SharedBuffer::default();
+    let mut io = NativeIo::new()
+        .with_error(Box::new(error.clone()))
+        .with_input(Box::new(io::Cursor::new(b"input".to_vec())));
+    let mut input = String::new();
+
+    io.std_streams().input().read_to_string(&mut input).unwrap();
+    write!(io.std_streams().buffered_error(), "buffered").unwrap();
+    drop(io);
+
+    assert_eq!("input", input);
+    assert_eq!(b"buffered".to_vec(), *error.0.lock().unwrap());
+}
+
+#[test]
+fn native_io__lock_output_with_replaced_output__writes_captured() {
+    let output = SharedBuffer::default();
+    let mut io = NativeIo::new().with_output(Box::new(output.clone()));
+    write!(io.std_streams().buffered_output(), "first ").unwrap();
+
+    {
+        let mut locked = io.lock_output();
+        write!(locked, "second").unwrap();
+    }
+
+    assert_eq!(b"first second".to_vec(), *output.0.lock().unwrap());
+}
+
+#[test]
+fn native_io__buffered_output__flushed_by_flush_all() {
+    let output = SharedBuffer::default();
+    let mut io = NativeIo::new().with_output(Box::new(output.clone()));
+
+    write!(io.buffered_output(), "buffered").unwrap();
+    write!(io.buffered_error(), "").unwrap();
+    assert!(output.0.lock().unwrap().is_empty());
+    io.flush_all().unwrap();
+
+    assert_eq!(b"buffered".to_vec(), *output.0.lock().unwrap());
+}
+
+#[test]
+fn native_io__automatic_flush_fails__error_returned_by_next_write() {
+    let mut io = NativeIo::new().with_output(Box::new(FailingWriter));
+    write!(io.buffered_output(), "lost").unwrap();
+
+    let result = io.std_streams().output().write(b"unbuffered");
+
+    assert_eq!("write failed", result.unwrap_err().to_string());
+}
+
+#[test]
+fn native_std_streams__into_output__buffered_data_flushed_first() {
+    let output = SharedBuffer::default();
+    let mut streams = NativeStdStreams::new().with_output(Box::new(output.clone()));
+    write!(streams.buffered_output(), "first ").unwrap();
+
+    let mut owned = streams.into_output().unwrap();
+    write!(owned, "second").unwrap();
+
+    assert_eq!(b"first second".to_vec(), *output.0.lock().unwrap());
+}
+
+#[test]
+fn native_std_streams__into_error_flush_fails__error_returned() {
+    let mut streams = NativeStdStreams::new().with_error(Box::new(FailingWriter));
+    write!(streams.buffered_error(), "lost").unwrap();
+
+    let result = streams.into_error();
+
+    assert_eq!("write failed", result.err().unwrap().to_string());
+}
@@ -0,0 +1,125 @@
+#![allow(non_snake_case)]
+
+extern crate io_providers;
+
+use std::path::Path;
+
+use io_providers::{
+    Env, Fs, Io, SimulatedEnv, SimulatedIo, SimulatedStdStreams, StdStreams, TempFs,
+};
+
+fn configured_env() -> SimulatedEnv {
+    let mut env = SimulatedEnv::new();
+    env.set_current_dir("/home/user").unwrap();
+    env.set_var("APP_MODE", "test");
+    env
+}
+
+fn configured_fs() -> TempFs {
+    TempFs::from_layout(&[("/etc/app.conf", Some("verbose = true"))]).unwrap()
+}
+
+#[test]
+fn simulated_io__from_parts__parts_reachable_and_returned() {
+    let mut io = SimulatedIo::from_parts(
+        configured_env(),
+        configured_fs(),
+        SimulatedStdStreams::new(),
+    );
+
+    assert_eq!("test", io.env().var("APP_MODE").unwrap());
+    assert_eq!(
+        "verbose = true",
+        io.fs().read_to_string("/etc/app.conf").unwrap()
+    );
+    // Relative paths are resolved from the root of the TempFs, not the env's current directory
+    assert!(io.fs().exists("etc/app.conf"));
+    write!(io.std_streams().output(), "done").unwrap();
+
+    let (env, fs, stream) = io.into_parts();
+    assert_eq!(Path::new("/home/user"), env.current_dir().unwrap());
+    assert!(fs.exists("/etc/app.conf"));
+    assert_eq!("done", stream.read_output_string().unwrap());
+}
+
+#[test]
+fn simulated_io__lock_output__writes_captured() {
+    use std::io::Write;
+
+    let mut io = SimulatedIo::new().unwrap();
+
+    {
+        let mut locked = io.lock_output();
+        writeln!(locked, "line 1").unwrap();
+        writeln!(locked, "line 2").unwrap();
+    }
+
+    assert_eq!(
+        "line 1\nline 2\n",
+        io.std_streams().read_output_string().unwrap()
+    );
+}
+
+#[test]
+fn simulated_io__reset__state_cleared_and_configuration_kept() {
+    use io_providers::LineEnding;
+
+    let mut io = SimulatedIo::new().unwrap();
+    io.env_mut().set_case_insensitive_vars(false);
+    io.env_mut().set_var("PHASE", "1");
+    io.env_mut().set_current_dir("/work").unwrap();
+    let _ = io.env().var("HOME");
+    io.fs_mut().set_max_nodes(Some(10));
+    io.fs_mut().create_dir_all("/a/b").unwrap();
+    io.fs_mut().write("/a/b/data.txt", "phase 1").unwrap();
+    io.std_streams().set_line_ending(LineEnding::CrLf);
+    io.std_streams().write_input(b"input");
+    writeln!(io.std_streams().output(), "output").unwrap();
+    writeln!(io.std_streams().error(), "error").unwrap();
+
+    io.reset().unwrap();
+
+    assert!(io.env().var_reads().is_empty());
+    assert!(io.env().var_os("PHASE").is_none());
+    assert!(!io.env().has_current_dir());
+    assert_eq!(0, io.fs().file_count().unwrap());
+    assert_eq!(0, io.fs().dir_count().unwrap());
+    assert_eq!(0, io.fs().write_count());
+    assert!(io.std_streams().read_output().is_empty());
+    assert!(io.std_streams().read_error().is_empty());
+    assert_eq!(0, io.std_streams().output_write_count());
+    let mut input = Vec::new();
+    io.std_streams().input().read_to_end(&mut input).unwrap();
+    assert!(input.is_empty());
+
+    // Configuration is preserved
+    io.fs_mut().write("/x.txt", "").unwrap();
+    for i in 0..9 {
+        io.fs_mut().write(format!("/{}.txt", i), "").unwrap();
+    }
+    assert!(io.fs_mut().write("/too_many.txt", "").is_err());
+    writeln!(io.std_streams().output(), "phase 2").unwrap();
+    assert_eq!(b"phase 2\r\n", io.std_streams().read_output());
+}
+
+#[test]
+fn simulated_io__shared_by_reference__is_sync() {
+    fn assert_sync<T: Sync>() {}
+
+    assert_sync::<SimulatedEnv>();
+    assert_sync::<SimulatedIo>();
+}
+
+#[test]
+fn simulated_io__flush_all__buffered_data_captured_and_flushes_counted() {
+    let mut io = SimulatedIo::new().unwrap();
+
+    write!(io.buffered_output(), "out").unwrap();
+    write!(io.buffered_error(), "err").unwrap();
+    io.flush_all().unwrap();
+
+    assert_eq!(b"out", io.std_streams().read_output());
+    assert_eq!(b"err", io.std_streams().read_error());
+    assert_eq!(1, io.std_streams().output_flush_count());
+    assert_eq!(1, io.std_streams().error_flush_count());
+}
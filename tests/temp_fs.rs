@@ -3,9 +3,13 @@
 extern crate io_providers;
 
 use std::env;
-use std::path::PathBuf;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use io_providers::fs::{Fs, TempFs};
+use io_providers::fs::{DirEntryExt, Fs, NormalizationPolicy, OpenOptions, TempFs};
+use io_providers::LineEnding;
 
 #[test]
 fn fs__uses_system_temp_dir() {
@@ -41,3 +45,824 @@ fn fs__dropped_from_scope__cleans_up_temp_dir() {
 
     assert!(!temp_dir.exists());
 }
+
+#[test]
+fn fs__crlf_line_ending__write_translates_newlines() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    fs.set_line_ending(LineEnding::CrLf);
+
+    fs.write("test.txt", "a\nb\n").expect("Failed to write test file");
+    let contents = fs.read("test.txt").expect("Failed to read test file");
+
+    assert_eq!(b"a\r\nb\r\n".to_vec(), contents);
+}
+
+#[test]
+fn fs__passthrough_line_ending__write_untouched() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+
+    fs.write("test.txt", "a\nb\r\n").expect("Failed to write test file");
+    let contents = fs.read("test.txt").expect("Failed to read test file");
+
+    assert_eq!(b"a\nb\r\n".to_vec(), contents);
+}
+
+#[test]
+fn fs__absolute_path__resolved_from_root() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+
+    fs.write("/test.txt", "contents")
+        .expect("Failed to write test file");
+    let contents = fs
+        .read_to_string("test.txt")
+        .expect("Failed to read test file contents");
+
+    assert!(fs.path().join("test.txt").exists());
+    assert_eq!("contents", contents);
+}
+
+#[test]
+fn fs__path_traverses_out_of_root__is_invalid() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+
+    let result = fs.write("/../test.txt", "contents");
+
+    assert!(result.is_err());
+    assert!(!fs.path().parent().unwrap().join("test.txt").exists());
+}
+
+#[test]
+#[cfg(windows)]
+fn fs__windows_path_spellings__resolve_to_same_file() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+
+    fs.write("\\test.txt", "contents")
+        .expect("Failed to write test file");
+
+    for path in &[
+        "test.txt",
+        "C:\\test.txt",
+        "c:\\test.txt",
+        "C:test.txt",
+        "\\\\?\\C:\\test.txt",
+    ] {
+        let contents = fs
+            .read_to_string(path)
+            .expect("Failed to read test file contents");
+        assert_eq!("contents", contents);
+    }
+}
+
+#[test]
+#[cfg(windows)]
+fn fs__windows_other_drive__is_invalid() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+
+    let result = fs.write("D:\\test.txt", "contents");
+
+    assert!(result.is_err());
+}
+
+#[test]
+#[cfg(windows)]
+fn fs__windows_default_drive_changed__resolves_new_drive() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    fs.set_default_drive('d');
+
+    fs.write("D:\\test.txt", "contents")
+        .expect("Failed to write test file");
+
+    assert!(fs.write("C:\\test.txt", "contents").is_err());
+    assert_eq!("contents", fs.read_to_string("\\test.txt").unwrap());
+}
+
+#[test]
+#[should_panic(expected = "is not a valid drive letter")]
+fn fs__non_ascii_default_drive__panics() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    fs.set_default_drive('\u{00c4}');
+}
+
+#[test]
+fn fs__known_operations__counters_reflect_contents() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+
+    fs.create_dir("a").unwrap();
+    fs.create_dir("a/b").unwrap();
+    fs.write("a/one.txt", "12345").unwrap();
+    fs.write("a/b/two.txt", "123").unwrap();
+    fs.write("three.txt", "").unwrap();
+    fs.remove_file("three.txt").unwrap();
+    fs.copy("a/one.txt", "a/b/copy.txt").unwrap();
+
+    assert_eq!(3, fs.file_count().unwrap());
+    assert_eq!(2, fs.dir_count().unwrap());
+    assert_eq!(13, fs.total_bytes().unwrap());
+}
+
+#[test]
+fn fs__max_nodes_reached__creation_fails() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    fs.set_max_nodes(Some(2));
+
+    fs.create_dir("a").unwrap();
+    fs.write("a/one.txt", "1").unwrap();
+    let result = fs.write("a/two.txt", "2");
+
+    assert!(result.unwrap_err().to_string().contains("limit of 2"));
+    assert!(!fs.exists("a/two.txt"));
+    fs.write("a/one.txt", "overwritten")
+        .expect("Overwriting an existing file should not be limited");
+}
+
+#[test]
+fn fs__max_nodes_after_removals_and_renames__count_kept_up_to_date() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    fs.write("existing.txt", "").unwrap();
+    fs.set_max_nodes(Some(3));
+
+    fs.create_dir_all("a/b").unwrap();
+    assert!(fs.write("a/b/full.txt", "").is_err());
+    fs.remove_dir_all("a").unwrap();
+    fs.write("one.txt", "").unwrap();
+    fs.write("two.txt", "").unwrap();
+    fs.rename("one.txt", "two.txt").unwrap();
+    fs.copy("two.txt", "three.txt").unwrap();
+
+    assert!(fs.write("four.txt", "").is_err());
+    assert_eq!(3, fs.file_count().unwrap());
+}
+
+fn set_accessed(fs: &TempFs, path: &str, accessed: SystemTime) {
+    fs::File::open(fs.path().join(path))
+        .unwrap()
+        .set_times(fs::FileTimes::new().set_accessed(accessed))
+        .unwrap();
+}
+
+#[test]
+fn fs__update_atime_disabled__read_preserves_accessed_time() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    let past = UNIX_EPOCH + Duration::from_secs(1_000_000);
+    fs.write("test.txt", "contents").unwrap();
+    set_accessed(&fs, "test.txt", past);
+
+    fs.read("test.txt").unwrap();
+    fs.read_to_string("test.txt").unwrap();
+
+    let accessed = fs.metadata("test.txt").unwrap().accessed().unwrap();
+    assert_eq!(past, accessed);
+}
+
+#[test]
+fn fs__update_atime_enabled__read_advances_accessed_time() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    let past = UNIX_EPOCH + Duration::from_secs(1_000_000);
+    fs.set_update_atime(true);
+    fs.write("test.txt", "contents").unwrap();
+    set_accessed(&fs, "test.txt", past);
+
+    fs.read("test.txt").unwrap();
+
+    let accessed = fs.metadata("test.txt").unwrap().accessed().unwrap();
+    assert!(accessed > past);
+}
+
+#[test]
+fn fs__update_atime_enabled__open_for_read_advances_accessed_time() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    let past = UNIX_EPOCH + Duration::from_secs(1_000_000);
+    fs.set_update_atime(true);
+    fs.write("test.txt", "contents").unwrap();
+    set_accessed(&fs, "test.txt", past);
+
+    fs.open("test.txt", OpenOptions::new().read(true)).unwrap();
+
+    let accessed = fs.metadata("test.txt").unwrap().accessed().unwrap();
+    assert!(accessed > past);
+}
+
+fn read_through_view(fs: &dyn io_providers::fs::FsRead, path: &str) -> (bool, Vec<u8>) {
+    let path = Path::new(path);
+    (fs.exists(path), fs.read(path).unwrap_or_default())
+}
+
+#[test]
+fn fs__readonly_view__reads_same_files() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    fs.write("/test.txt", "contents").unwrap();
+
+    let (exists, contents) = read_through_view(fs.readonly(), "/test.txt");
+    let (missing_exists, _) = read_through_view(fs.readonly(), "/missing.txt");
+
+    assert!(exists);
+    assert_eq!(b"contents".to_vec(), contents);
+    assert!(!missing_exists);
+}
+
+#[test]
+fn fs__recursive_size_of_nested_tree__sums_file_lengths() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    fs.create_dir("a").unwrap();
+    fs.create_dir("a/b").unwrap();
+    fs.create_dir("a/b/empty").unwrap();
+    fs.write("a/one.txt", "1").unwrap();
+    fs.write("a/b/two.txt", "22").unwrap();
+    fs.write("a/b/three.txt", "").unwrap();
+    fs.write("four.txt", "4444").unwrap();
+
+    assert_eq!(3, fs.recursive_size("a").unwrap());
+    assert_eq!(7, fs.recursive_size("/").unwrap());
+    assert_eq!(4, fs.recursive_size("four.txt").unwrap());
+}
+
+#[cfg(unix)]
+#[test]
+fn fs__recursive_size_with_symlinks__counts_file_targets_only() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    fs.create_dir("a").unwrap();
+    fs.write("a/file.txt", "123").unwrap();
+    let root = fs.path().to_path_buf();
+    ::std::os::unix::fs::symlink(root.join("a"), root.join("a/dir_link")).unwrap();
+    ::std::os::unix::fs::symlink(root.join("a/file.txt"), root.join("a/file_link")).unwrap();
+    ::std::os::unix::fs::symlink(root.join("missing"), root.join("a/broken_link")).unwrap();
+
+    assert!(fs
+        .symlink_metadata("a/dir_link")
+        .unwrap()
+        .file_type()
+        .is_symlink());
+    assert_eq!(root.join("a"), fs.read_link("a/dir_link").unwrap());
+    assert_eq!(6, fs.recursive_size("a").unwrap());
+}
+
+#[test]
+fn fs__write_with_missing_parent__not_found() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+
+    let result = fs.write("/missing_dir/file.txt", "contents");
+
+    assert_eq!(io::ErrorKind::NotFound, result.unwrap_err().kind());
+}
+
+#[test]
+fn fs__write_while_read_only__permission_denied() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    fs.write("existing.txt", "contents").unwrap();
+    fs.set_read_only(true);
+
+    let result = fs.write("new.txt", "contents");
+    let open_result = fs.open("existing.txt", OpenOptions::new().append(true));
+
+    assert_eq!(io::ErrorKind::PermissionDenied, result.unwrap_err().kind());
+    assert_eq!(
+        io::ErrorKind::PermissionDenied,
+        open_result.unwrap_err().kind()
+    );
+    assert!(!fs.exists("new.txt"));
+    assert_eq!(b"contents".to_vec(), fs.read("existing.txt").unwrap());
+}
+
+#[test]
+fn fs__open_create_new_existing_file__already_exists() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    fs.write("existing.txt", "contents").unwrap();
+
+    let result = fs.open(
+        "existing.txt",
+        OpenOptions::new().write(true).create_new(true),
+    );
+
+    assert_eq!(io::ErrorKind::AlreadyExists, result.unwrap_err().kind());
+}
+
+#[test]
+fn fs__content_hash__stable_across_reads_and_changed_by_write() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    fs.write("test.txt", "one").unwrap();
+
+    let first = fs.content_hash("test.txt").unwrap();
+    let _ = fs.read("test.txt").unwrap();
+    let second = fs.content_hash("test.txt").unwrap();
+    fs.write("test.txt", "two").unwrap();
+    let third = fs.content_hash("test.txt").unwrap();
+
+    assert_eq!(first, second);
+    assert_ne!(first, third);
+}
+
+#[cfg(unix)]
+#[test]
+fn fs__restrictive_umask__applied_to_new_files_and_dirs() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    fs.write("existing.txt", "contents").unwrap();
+    let existing_mode = fs.metadata("existing.txt").unwrap().permissions().mode();
+    fs.set_umask(Some(0o027));
+
+    fs.write("written.txt", "contents").unwrap();
+    fs.open("opened.txt", OpenOptions::new().write(true).create(true))
+        .unwrap();
+    fs.create_dir("dir").unwrap();
+    fs.write("existing.txt", "new contents").unwrap();
+
+    let mode = |path: &str| fs.metadata(path).unwrap().permissions().mode() & 0o777;
+    assert_eq!(0o640, mode("written.txt"));
+    assert_eq!(0o640, mode("opened.txt"));
+    assert_eq!(0o750, mode("dir"));
+    assert_eq!(existing_mode & 0o777, mode("existing.txt"));
+}
+
+#[cfg(unix)]
+#[test]
+fn fs__non_utf8_file_name__preserved_by_read_dir() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    let name = OsStr::from_bytes(b"caf\xe9.txt");
+    fs.write(Path::new("/").join(name), "contents").unwrap();
+
+    let entries = fs
+        .read_dir("/")
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    assert_eq!(1, entries.len());
+    assert_eq!(name, entries[0].file_name());
+    assert_eq!(Some(name), entries[0].path().file_name());
+    assert_eq!("caf\u{FFFD}.txt", entries[0].file_name_lossy());
+    assert_eq!(
+        b"contents".to_vec(),
+        fs.read(Path::new("/").join(name)).unwrap()
+    );
+}
+
+fn load_settings<F: Fs>(fs: &F, names: &[&str]) -> Vec<String> {
+    names
+        .iter()
+        .filter(|name| fs.metadata(name).is_ok())
+        .map(|name| fs.read_to_string(name).unwrap())
+        .collect()
+}
+
+#[test]
+fn fs__counters__count_operations_until_reset() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    fs.write("a.txt", "a").unwrap();
+    fs.write("b.txt", "b").unwrap();
+    assert_eq!(2, fs.write_count());
+    fs.reset_counters();
+
+    let settings = load_settings(&fs, &["a.txt", "b.txt", "missing.txt"]);
+    fs.open("a.txt", OpenOptions::new().read(true)).unwrap();
+
+    assert_eq!(vec!["a", "b"], settings);
+    assert_eq!(2, fs.read_count());
+    assert_eq!(3, fs.metadata_count());
+    assert_eq!(1, fs.open_count());
+    assert_eq!(0, fs.write_count());
+}
+
+#[test]
+fn fs__remove_dir_all_with_injected_error__error_names_nested_path() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    fs.create_dir("a").unwrap();
+    fs.create_dir("a/b").unwrap();
+    fs.write("a/b/locked.txt", "").unwrap();
+    fs.inject_error("a/b/locked.txt", io::ErrorKind::PermissionDenied)
+        .unwrap();
+
+    let error = fs.remove_dir_all("a").unwrap_err();
+
+    assert_eq!(io::ErrorKind::PermissionDenied, error.kind());
+    assert!(
+        error
+            .to_string()
+            .contains(&format!("{}", Path::new("a/b/locked.txt").display())),
+        "{}",
+        error
+    );
+    assert!(fs.exists("a/b/locked.txt"));
+    fs.remove_dir_all("a").unwrap();
+    assert!(!fs.exists("a"));
+}
+
+#[test]
+fn fs__remove_dir_all_fails__error_source_kept() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    fs.create_dir("a").unwrap();
+    fs.write("a/locked.txt", "").unwrap();
+    fs.inject_error("a/locked.txt", io::ErrorKind::PermissionDenied)
+        .unwrap();
+
+    let error = fs.remove_dir_all("a").unwrap_err();
+
+    let source = std::error::Error::source(error.get_ref().unwrap()).unwrap();
+    assert_eq!("injected error", source.to_string());
+}
+
+#[test]
+fn fs__remove_dir_all__metadata_count_unchanged() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    fs.create_dir_all("a/b").unwrap();
+    fs.write("a/b/file.txt", "").unwrap();
+
+    fs.remove_dir_all("a").unwrap();
+    let _ = fs.remove_dir_all_best_effort("a");
+
+    assert_eq!(0, fs.metadata_count());
+}
+
+#[test]
+fn fs__remove_dir_all_of_file__not_a_directory_and_file_kept() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    fs.write("test.txt", "contents").unwrap();
+
+    let error = fs.remove_dir_all("test.txt").unwrap_err();
+    let failures = fs.remove_dir_all_best_effort("test.txt").unwrap_err();
+
+    assert_eq!(io::ErrorKind::NotADirectory, error.kind());
+    assert_eq!(1, failures.len());
+    assert_eq!(io::ErrorKind::NotADirectory, failures[0].1.kind());
+    assert!(fs.exists("test.txt"));
+}
+
+#[test]
+fn fs__remove_dir_all_best_effort__removes_everything_else() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    fs.create_dir("a").unwrap();
+    fs.create_dir("a/b").unwrap();
+    fs.create_dir("a/c").unwrap();
+    fs.write("a/b/locked.txt", "").unwrap();
+    fs.write("a/b/other.txt", "").unwrap();
+    fs.write("a/c/file.txt", "").unwrap();
+    fs.inject_error("a/b/locked.txt", io::ErrorKind::PermissionDenied)
+        .unwrap();
+
+    let failures = fs.remove_dir_all_best_effort("a").unwrap_err();
+
+    assert_eq!(1, failures.len());
+    assert_eq!(Path::new("a/b/locked.txt"), failures[0].0);
+    assert_eq!(io::ErrorKind::PermissionDenied, failures[0].1.kind());
+    assert!(fs.exists("a/b/locked.txt"));
+    assert!(!fs.exists("a/b/other.txt"));
+    assert!(!fs.exists("a/c"));
+}
+
+#[cfg(unix)]
+#[test]
+fn fs__metadata_of_dangling_symlink__not_found() {
+    let fs = TempFs::new().expect("Failed to create new TempFs");
+    let root = fs.path().to_path_buf();
+    ::std::os::unix::fs::symlink(root.join("missing"), root.join("dangling")).unwrap();
+
+    let result = fs.metadata("dangling");
+
+    assert_eq!(io::ErrorKind::NotFound, result.unwrap_err().kind());
+    assert!(fs
+        .symlink_metadata("dangling")
+        .unwrap()
+        .file_type()
+        .is_symlink());
+}
+
+#[cfg(unix)]
+#[test]
+fn fs__metadata_of_symlink_cycle__loop_error() {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const ELOOP: i32 = 40;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    const ELOOP: i32 = 62;
+
+    let fs = TempFs::new().expect("Failed to create new TempFs");
+    let root = fs.path().to_path_buf();
+    ::std::os::unix::fs::symlink(root.join("b"), root.join("a")).unwrap();
+    ::std::os::unix::fs::symlink(root.join("c"), root.join("b")).unwrap();
+    ::std::os::unix::fs::symlink(root.join("a"), root.join("c")).unwrap();
+
+    let error = fs.metadata("a").unwrap_err();
+
+    assert_eq!(Some(ELOOP), error.raw_os_error());
+    assert_ne!(io::ErrorKind::NotFound, error.kind());
+    for link in &["a", "b", "c"] {
+        assert!(fs.symlink_metadata(link).unwrap().file_type().is_symlink());
+    }
+}
+
+#[test]
+fn fs__create_dir_all__creates_missing_ancestors() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    fs.create_dir("a").unwrap();
+
+    fs.create_dir_all("/a/b/./c/../d").unwrap();
+    fs.create_dir_all("a/b").unwrap();
+
+    assert!(fs.metadata("a/b/c").unwrap().is_dir());
+    assert!(fs.metadata("a/b/d").unwrap().is_dir());
+}
+
+#[test]
+fn fs__create_dir_all_out_of_root__is_invalid() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+
+    assert!(fs.create_dir_all("../../escaped").is_err());
+    assert!(!fs.path().parent().unwrap().join("escaped").exists());
+}
+
+#[test]
+fn fs__from_layout_three_levels__files_and_dirs_created() {
+    let fs = TempFs::from_layout(&[
+        ("/a/one.txt", Some("1")),
+        ("/a/b/two.txt", Some("2")),
+        ("/a/b/c/three.txt", Some("3")),
+        ("/a/b/empty", None),
+    ])
+    .expect("Failed to create TempFs from layout");
+
+    let mut listing = fs
+        .read_dir("/a/b")
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    listing.sort();
+
+    assert_eq!("1", fs.read_to_string("/a/one.txt").unwrap());
+    assert_eq!("2", fs.read_to_string("/a/b/two.txt").unwrap());
+    assert_eq!("3", fs.read_to_string("/a/b/c/three.txt").unwrap());
+    assert_eq!(vec!["c", "empty", "two.txt"], listing);
+}
+
+#[test]
+fn fs__default_normalization_policy__redundant_separators_and_trailing_slash_ignored() {
+    let mut fs = TempFs::new().unwrap();
+    fs.write("/a.txt", "contents").unwrap();
+
+    assert_eq!("contents", fs.read_to_string("//a.txt").unwrap());
+    assert_eq!("contents", fs.read_to_string("/./a.txt").unwrap());
+    assert!(fs.metadata("/a.txt/").unwrap().is_file());
+    fs.create_dir_all("/x//y/").unwrap();
+    assert!(fs.metadata("/x/y").unwrap().is_dir());
+}
+
+#[test]
+fn fs__no_collapse_separators_policy__repeated_separator_is_invalid() {
+    let mut fs = TempFs::new().unwrap();
+    fs.create_dir("/dir").unwrap();
+    fs.write("/dir/a.txt", "contents").unwrap();
+    fs.set_normalization_policy(NormalizationPolicy {
+        collapse_separators: false,
+        ..NormalizationPolicy::default()
+    });
+
+    assert_eq!(
+        io::ErrorKind::Other,
+        fs.read("/dir//a.txt").unwrap_err().kind()
+    );
+    assert_eq!(
+        io::ErrorKind::Other,
+        fs.create_dir_all("/x//y").unwrap_err().kind()
+    );
+    assert!(!fs.exists("/dir//a.txt"));
+    assert_eq!("contents", fs.read_to_string("/dir/a.txt").unwrap());
+    assert!(fs.metadata("/dir/").unwrap().is_dir());
+}
+
+#[test]
+fn fs__keep_cur_dir_policy__dot_component_is_invalid() {
+    let mut fs = TempFs::new().unwrap();
+    fs.write("/a.txt", "contents").unwrap();
+    fs.set_normalization_policy(NormalizationPolicy {
+        remove_cur_dir: false,
+        ..NormalizationPolicy::default()
+    });
+
+    assert_eq!(
+        io::ErrorKind::Other,
+        fs.read("/./a.txt").unwrap_err().kind()
+    );
+    assert_eq!("contents", fs.read_to_string("/a.txt").unwrap());
+}
+
+#[test]
+fn fs__keep_trailing_separator_policy__trailing_slash_requires_directory() {
+    let mut fs = TempFs::new().unwrap();
+    fs.write("/a.txt", "contents").unwrap();
+    fs.set_normalization_policy(NormalizationPolicy {
+        strip_trailing_separator: false,
+        ..NormalizationPolicy::default()
+    });
+
+    assert!(fs.metadata("/a.txt/").is_err());
+    assert!(!fs.exists("/a.txt/"));
+    assert!(fs.write("/b.txt/", "contents").is_err());
+    assert!(!fs.exists("/b.txt"));
+    fs.create_dir("/dir/").unwrap();
+    assert!(fs.metadata("/dir/").unwrap().is_dir());
+    assert!(fs.metadata("//dir").unwrap().is_dir());
+}
+
+#[test]
+#[should_panic(expected = "1 injected error(s) were never triggered: /b.txt (PermissionDenied)")]
+fn fs__verify_with_unfired_injection__panics_listing_it() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    fs.write("a.txt", "").unwrap();
+    fs.inject_error("a.txt", io::ErrorKind::NotFound).unwrap();
+    fs.inject_error("b.txt", io::ErrorKind::PermissionDenied)
+        .unwrap();
+
+    assert!(fs.read("a.txt").is_err());
+    fs.verify();
+}
+
+#[test]
+fn fs__verify_with_all_injections_fired__ok() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    fs.set_strict_injections(true);
+    fs.write("a.txt", "").unwrap();
+    fs.inject_error("a.txt", io::ErrorKind::NotFound).unwrap();
+
+    assert!(fs.read("a.txt").is_err());
+    fs.verify();
+}
+
+#[test]
+#[should_panic(expected = "never triggered")]
+fn fs__strict_injections_dropped_with_unfired_injection__panics() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    fs.set_strict_injections(true);
+    fs.inject_error("a.txt", io::ErrorKind::NotFound).unwrap();
+}
+
+#[test]
+#[should_panic(expected = "never triggered")]
+fn fs__strict_injections_reset_with_unfired_injection__panics() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    fs.set_strict_injections(true);
+    fs.inject_error("a.txt", io::ErrorKind::NotFound).unwrap();
+
+    let _ = fs.reset();
+}
+
+#[test]
+fn fs__create_sized_4gib__reports_length_and_reads_zeros() {
+    use std::io::{Read, Seek, SeekFrom, Write};
+
+    const LEN: u64 = 4 << 30;
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+
+    fs.create_sized("huge.bin", LEN).unwrap();
+    let mut file = fs
+        .open("huge.bin", OpenOptions::new().read(true).write(true))
+        .unwrap();
+    file.seek(SeekFrom::Start(LEN - 4)).unwrap();
+    file.write_all(b"tail").unwrap();
+    let mut head = [0xffu8; 16];
+    file.seek(SeekFrom::Start(1 << 30)).unwrap();
+    file.read_exact(&mut head).unwrap();
+    let mut tail = Vec::new();
+    file.seek(SeekFrom::Start(LEN - 4)).unwrap();
+    file.read_to_end(&mut tail).unwrap();
+
+    assert_eq!(LEN, fs.metadata("huge.bin").unwrap().len());
+    assert_eq!([0u8; 16], head);
+    assert_eq!(b"tail".to_vec(), tail);
+}
+
+#[test]
+fn fs__path_longer_than_max_path_len__invalid_input() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    // Each component is short enough for the host
+    let dir = format!("/{}", "d".repeat(99)).repeat(2);
+    let path = format!("{}/{}", dir, "f".repeat(99));
+    assert_eq!(300, path.len());
+    fs.create_dir_all(&dir).unwrap();
+
+    fs.set_max_path_len(Some(260));
+    assert_eq!(
+        io::ErrorKind::InvalidInput,
+        fs.write(&path, "contents").unwrap_err().kind()
+    );
+    assert!(!fs.exists(&path));
+
+    fs.set_max_path_len(None);
+    fs.write(&path, "contents").unwrap();
+    assert!(fs.exists(&path));
+}
+
+#[test]
+fn fs__read_transform__read_reflects_transform() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    fs.write("a.txt", "hello").unwrap();
+    fs.write("b.txt", "world").unwrap();
+
+    fs.set_read_transform("a.txt", Box::new(|bytes| bytes.to_ascii_uppercase()))
+        .unwrap();
+
+    assert_eq!("HELLO", fs.read_to_string("a.txt").unwrap());
+    assert_eq!(b"HELLO".to_vec(), fs.read("a.txt").unwrap());
+    assert_eq!("world", fs.read_to_string("b.txt").unwrap());
+    assert_eq!(
+        "hello",
+        fs::read_to_string(fs.path().join("a.txt")).unwrap()
+    );
+}
+
+#[test]
+fn fs__global_read_transform__applied_after_path_transform() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    fs.write("a.txt", "hello").unwrap();
+    fs.write("b.txt", "world").unwrap();
+
+    fs.set_read_transform("a.txt", Box::new(|bytes| bytes.to_ascii_uppercase()))
+        .unwrap();
+    fs.set_global_read_transform(Some(Box::new(|bytes| {
+        let mut bytes = bytes.to_vec();
+        bytes.push(b'!');
+        bytes
+    })));
+
+    assert_eq!("HELLO!", fs.read_to_string("a.txt").unwrap());
+    assert_eq!("world!", fs.read_to_string("b.txt").unwrap());
+    fs.inject_error("a.txt", io::ErrorKind::PermissionDenied)
+        .unwrap();
+    assert_eq!(
+        io::ErrorKind::PermissionDenied,
+        fs.read("a.txt").unwrap_err().kind()
+    );
+    fs.clear_read_transforms();
+    assert_eq!("hello", fs.read_to_string("a.txt").unwrap());
+}
+
+#[test]
+fn fs__read_transform_produces_invalid_utf8__read_to_string_is_invalid_data() {
+    let mut fs = TempFs::new().expect("Failed to create new TempFs");
+    fs.write("a.txt", "hello").unwrap();
+
+    fs.set_read_transform("a.txt", Box::new(|_| vec![0xff]))
+        .unwrap();
+
+    assert_eq!(
+        io::ErrorKind::InvalidData,
+        fs.read_to_string("a.txt").unwrap_err().kind()
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn fs__metadata_at_parent_of_symlink__parent_of_target() {
+    let fs = TempFs::from_layout(&[
+        ("/base/config", Some("base")),
+        ("/x/y/config", Some("y")),
+        ("/x/config", Some("target parent")),
+    ])
+    .unwrap();
+    let root = fs.path();
+    ::std::os::unix::fs::symlink(root.join("x/y"), root.join("base/link")).unwrap();
+
+    let metadata = fs.metadata_at("/base", "link/../config").unwrap();
+
+    assert_eq!(13, metadata.len());
+}
+
+#[test]
+fn fs__at_methods__same_relative_path_resolved_against_each_base() {
+    use std::io::Read;
+
+    let mut fs = TempFs::from_layout(&[
+        ("/a/data/file.txt", Some("from a")),
+        ("/b/data/file.txt", Some("from b")),
+    ])
+    .unwrap();
+    let rel = Path::new("data/file.txt");
+
+    let mut contents = String::new();
+    fs.open_at("/a", rel, OpenOptions::new().read(true))
+        .unwrap()
+        .read_to_string(&mut contents)
+        .unwrap();
+    assert_eq!("from a", contents);
+    contents.clear();
+    fs.open_at("/b", rel, OpenOptions::new().read(true))
+        .unwrap()
+        .read_to_string(&mut contents)
+        .unwrap();
+    assert_eq!("from b", contents);
+
+    assert_eq!(6, fs.metadata_at("/b", rel).unwrap().len());
+    let names: Vec<_> = fs
+        .read_dir_at("/a", "data")
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .collect();
+    assert_eq!(vec!["file.txt"], names);
+    // `..` can't leave the root
+    assert_eq!(
+        6,
+        fs.metadata_at("/a", "../../../b/data/file.txt")
+            .unwrap()
+            .len()
+    );
+}